@@ -0,0 +1,240 @@
+//! ANSIエスケープシーケンス（SGR）で色を付けたログの表示
+
+use std::path::Path;
+
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::Span,
+};
+use ropey::Rope;
+
+use crate::buffer::char_display_width;
+
+/// エスケープシーケンスを解釈して表示するファイルの拡張子
+const ANSI_EXTENSIONS: &[&str] = &["log", "ansi"];
+
+/// SGRの30〜37 / 40〜47の色
+const BASIC_COLORS: [Color; 8] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::Gray,
+];
+
+/// SGRの90〜97 / 100〜107の明るい色
+const BRIGHT_COLORS: [Color; 8] = [
+    Color::DarkGray,
+    Color::LightRed,
+    Color::LightGreen,
+    Color::LightYellow,
+    Color::LightBlue,
+    Color::LightMagenta,
+    Color::LightCyan,
+    Color::White,
+];
+
+/// ANSIの色を付けて表示するか（.log / .ansi のファイル、またはエスケープ文字を含む内容）
+pub(crate) fn is_ansi_colored(path: &Path, buffer: &Rope) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ANSI_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        || buffer.chunks().any(|chunk| chunk.contains('\x1b'))
+}
+
+/// 各文字がエスケープシーケンスの一部か（CSIは終端の文字まで、OSCはBELかESC \ まで）
+pub(crate) fn escape_mask(text: &str) -> Vec<bool> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut mask = vec![false; chars.len()];
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] != '\x1b' {
+            i += 1;
+            continue;
+        }
+        let end = sequence_end(&chars, i);
+        mask[i..end].fill(true);
+        i = end;
+    }
+    mask
+}
+
+/// iから始まるエスケープシーケンスの直後の位置
+fn sequence_end(chars: &[char], i: usize) -> usize {
+    match chars.get(i + 1) {
+        Some('[') => chars[i + 2..].iter()
+            .position(|c| ('\x40'..='\x7e').contains(c))
+            .map_or(chars.len(), |p| i + 2 + p + 1),
+        Some(']') => {
+            let mut j = i + 2;
+            while j < chars.len() {
+                if chars[j] == '\x07' {
+                    return j + 1;
+                }
+                if chars[j] == '\x1b' && chars.get(j + 1) == Some(&'\\') {
+                    return j + 2;
+                }
+                j += 1;
+            }
+            chars.len()
+        }
+        Some('\n') | None => i + 1,
+        Some(_) => i + 2,
+    }
+}
+
+/// SGRのパラメータをスタイルに反映（0やパラメータなしはbaseに戻す）
+fn apply_sgr(style: Style, params: &str, base: Style) -> Style {
+    let codes: Vec<u16> = params.split(';').map(|p| p.parse().unwrap_or(0)).collect();
+    let mut style = style;
+    let mut i = 0;
+    while i < codes.len() {
+        match codes[i] {
+            0 => style = base,
+            1 => style = style.add_modifier(Modifier::BOLD),
+            2 => style = style.add_modifier(Modifier::DIM),
+            3 => style = style.add_modifier(Modifier::ITALIC),
+            4 => style = style.add_modifier(Modifier::UNDERLINED),
+            7 => style = style.add_modifier(Modifier::REVERSED),
+            9 => style = style.add_modifier(Modifier::CROSSED_OUT),
+            22 => style = style.remove_modifier(Modifier::BOLD | Modifier::DIM),
+            23 => style = style.remove_modifier(Modifier::ITALIC),
+            24 => style = style.remove_modifier(Modifier::UNDERLINED),
+            27 => style = style.remove_modifier(Modifier::REVERSED),
+            29 => style = style.remove_modifier(Modifier::CROSSED_OUT),
+            code @ 30..=37 => style.fg = Some(BASIC_COLORS[(code - 30) as usize]),
+            code @ 40..=47 => style.bg = Some(BASIC_COLORS[(code - 40) as usize]),
+            code @ 90..=97 => style.fg = Some(BRIGHT_COLORS[(code - 90) as usize]),
+            code @ 100..=107 => style.bg = Some(BRIGHT_COLORS[(code - 100) as usize]),
+            39 => style.fg = base.fg,
+            49 => style.bg = base.bg,
+            // 256色（38;5;n）とRGB（38;2;r;g;b）
+            code @ (38 | 48) => {
+                let color = match codes.get(i + 1) {
+                    Some(5) => {
+                        let color = codes.get(i + 2).map(|&n| Color::Indexed(n as u8));
+                        i += 2;
+                        color
+                    }
+                    Some(2) => {
+                        let rgb = codes.get(i + 2..i + 5).map(|c| Color::Rgb(c[0] as u8, c[1] as u8, c[2] as u8));
+                        i += 4;
+                        rgb
+                    }
+                    _ => None,
+                };
+                if code == 38 {
+                    style.fg = color.or(style.fg);
+                } else {
+                    style.bg = color.or(style.bg);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    style
+}
+
+/// SGRの色を解釈した行のSpan（エスケープシーケンス自体は表示しない）。
+/// start_col文字目から表示幅widthまでを変換し、overlayで文字ごとにスタイルを上書きできる（カーソルや選択範囲用）。
+/// 表示した最後の文字の次の文字インデックスと使った表示幅も返す
+pub(crate) fn ansi_to_spans(
+    text: &str,
+    base: Style,
+    start_col: usize,
+    width: usize,
+    overlay: impl Fn(usize, Style) -> Style,
+) -> (Vec<Span<'static>>, usize, usize) {
+    let chars: Vec<char> = text.chars().collect();
+    let mut spans = Vec::new();
+    let mut current: Option<(String, Style)> = None;
+    let mut style = base;
+    let mut used = 0;
+    let mut end_col = start_col;
+    let mut i = 0;
+    while i < chars.len() {
+        let ch = chars[i];
+        if ch == '\x1b' {
+            let end = sequence_end(&chars, i);
+            if chars.get(i + 1) == Some(&'[') && chars.get(end - 1) == Some(&'m') {
+                let params: String = chars[i + 2..end - 1].iter().collect();
+                style = apply_sgr(style, &params, base);
+            }
+            i = end;
+            end_col = end_col.max(i.min(chars.len()));
+            continue;
+        }
+        if ch == '\n' || ch == '\r' {
+            break;
+        }
+        if i >= start_col {
+            let ch_width = char_display_width(ch);
+            if used + ch_width > width {
+                break;
+            }
+            let char_style = overlay(i, style);
+            match current.as_mut() {
+                Some((_, s)) if *s == char_style => {}
+                _ => {
+                    if let Some((text, s)) = current.take() {
+                        spans.push(Span::styled(text, s));
+                    }
+                    current = Some((String::new(), char_style));
+                }
+            }
+            if let Some((text, _)) = current.as_mut() {
+                if ch == '\t' {
+                    text.push_str("    ");
+                } else {
+                    text.push(ch);
+                }
+            }
+            used += ch_width;
+            end_col = i + 1;
+        }
+        i += 1;
+    }
+    if let Some((text, s)) = current {
+        spans.push(Span::styled(text, s));
+    }
+    (spans, end_col, used)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(text: &str) -> Vec<(String, Style)> {
+        let (spans, _, _) = ansi_to_spans(text, Style::default(), 0, 80, |_, style| style);
+        spans.into_iter().map(|span| (span.content.into_owned(), span.style)).collect()
+    }
+
+    #[test]
+    fn sgr_colors_become_styles() {
+        let spans = render("\x1b[1;31mERROR\x1b[0m done");
+        assert_eq!(spans, vec![
+            ("ERROR".to_string(), Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+            (" done".to_string(), Style::default()),
+        ]);
+    }
+
+    #[test]
+    fn extended_colors_and_default_fg() {
+        let spans = render("\x1b[38;5;208ma\x1b[48;2;1;2;3mb\x1b[39mc");
+        assert_eq!(spans[0].1, Style::default().fg(Color::Indexed(208)));
+        assert_eq!(spans[1].1, Style::default().fg(Color::Indexed(208)).bg(Color::Rgb(1, 2, 3)));
+        assert_eq!(spans[2].1, Style::default().bg(Color::Rgb(1, 2, 3)));
+    }
+
+    #[test]
+    fn escape_sequences_are_hidden() {
+        let text = "a\x1b[2Kb\x1b]0;title\x07c";
+        assert_eq!(render(text).iter().map(|(t, _)| t.as_str()).collect::<String>(), "abc");
+        let mask = escape_mask(text);
+        assert_eq!(mask.iter().filter(|&&hidden| !hidden).count(), 3);
+    }
+}
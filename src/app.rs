@@ -0,0 +1,3382 @@
+//! エディタ全体の状態（タブ・ペイン・サイドバーなど）とキー・マウス操作の処理
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant, SystemTime};
+
+use crossterm::{
+    cursor::SetCursorStyle,
+    event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEventKind},
+};
+use ratatui::{
+    layout::{Direction, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+use ropey::Rope;
+use similar::{ChangeTag, TextDiff};
+use ratatui_image::{
+    picker::Picker,
+    protocol::StatefulProtocol,
+    thread::ThreadProtocol,
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::ansi::is_ansi_colored;
+use crate::buffer::{format_document, is_word_char, run_formatter, EditorPane, Selection};
+use crate::config::{
+    counterpart_rules, load_recent_files, load_sidebar_width, personal_dictionary_path, save_recent_files,
+    save_sidebar_width, Config,
+    DEFAULT_SCROLL_COLUMNS, DEFAULT_SCROLL_LINES, DEFAULT_SCROLL_MARGIN, DEFAULT_SIDEBAR_WIDTH_PERCENT,
+    RECENT_FILES_MAX, SIDEBAR_WIDTH_MAX_PERCENT, SIDEBAR_WIDTH_MIN_PERCENT,
+};
+use crate::file_finder::FileFinder;
+use crate::highlight::{Language, SyntaxHighlighter};
+use crate::spell::{word_ranges, Dictionary};
+use crate::image_view::{
+    decode_image, is_image_file, DecodeRequest, DecodeResult, ImageView, ResizeRequest, IMAGE_ZOOM_STEP,
+};
+use crate::keymap::{Action, KeyLookup, KeySpec, Keymap};
+use crate::terminal::TerminalPane;
+use crate::ui::UiColors;
+
+/// Base64エンコード（OSC 52用）
+pub(crate) fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result = String::new();
+    for chunk in data.chunks(3) {
+        let b0 = chunk[0] as usize;
+        let b1 = chunk.get(1).copied().unwrap_or(0) as usize;
+        let b2 = chunk.get(2).copied().unwrap_or(0) as usize;
+
+        result.push(ALPHABET[b0 >> 2] as char);
+        result.push(ALPHABET[((b0 & 0x03) << 4) | (b1 >> 4)] as char);
+        if chunk.len() > 1 {
+            result.push(ALPHABET[((b1 & 0x0f) << 2) | (b2 >> 6)] as char);
+        } else {
+            result.push('=');
+        }
+        if chunk.len() > 2 {
+            result.push(ALPHABET[b2 & 0x3f] as char);
+        } else {
+            result.push('=');
+        }
+    }
+    result
+}
+
+/// ステータスメッセージを表示しておく時間
+pub(crate) const STATUS_MESSAGE_DURATION: Duration = Duration::from_secs(4);
+
+/// ファイルサイズを読みやすい形式に変換（例: 512B, 1.2K, 3.4M）
+pub(crate) fn format_file_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["K", "M", "G", "T"];
+    if bytes < 1024 {
+        return format!("{}B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1}{}", size, UNITS[unit])
+}
+
+/// ディレクトリから親へ辿って最も近いリポジトリのgitディレクトリを探す
+pub(crate) fn find_git_dir(dir: &Path) -> Option<PathBuf> {
+    for ancestor in dir.ancestors() {
+        let git = ancestor.join(".git");
+        if git.is_dir() {
+            return Some(git);
+        }
+        if git.is_file() {
+            // サブモジュール・worktreeでは ".git" ファイルに実体の場所が書かれている（読めなければ親を探す）
+            let content = fs::read_to_string(&git).unwrap_or_default();
+            if let Some(git_dir) = content.trim().strip_prefix("gitdir:") {
+                return Some(ancestor.join(git_dir.trim()));
+            }
+        }
+    }
+    None
+}
+
+/// 対応するファイルを探すディレクトリ（同じ場所、テスト用のディレクトリ、src・tests間など）
+const COUNTERPART_DIRS: &[&str] = &["", "tests", "__tests__", "..", "../tests", "../src", "../include"];
+
+/// ".." を取り除いたパス（同じファイルを別のパスで候補にしないため）
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(normalized.components().next_back(), Some(Component::Normal(_))) => {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// 切り替え規則から対応するファイルの候補を優先順に列挙（存在するかは確認しない）
+pub(crate) fn counterpart_candidates(path: &Path, rules: &[(String, Vec<String>)]) -> Vec<PathBuf> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name().and_then(|n| n.to_str())) else {
+        return Vec::new();
+    };
+    let Some((suffix, replacements)) = rules.iter()
+        .find(|(suffix, _)| name.len() > suffix.len() && name.ends_with(suffix.as_str()))
+    else {
+        return Vec::new();
+    };
+    let stem = &name[..name.len() - suffix.len()];
+    let path = normalize_path(path);
+    let mut candidates = Vec::new();
+    for replacement in replacements {
+        let file_name = format!("{stem}{replacement}");
+        for sub in COUNTERPART_DIRS {
+            let candidate = normalize_path(&dir.join(sub).join(&file_name));
+            if candidate != path && !candidates.contains(&candidate) {
+                candidates.push(candidate);
+            }
+        }
+    }
+    candidates
+}
+
+/// HEADからブランチ名を取得（detached HEADはハッシュの先頭7桁）
+pub(crate) fn read_git_branch(git_dir: &Path) -> Option<String> {
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    Some(match head.strip_prefix("ref: refs/heads/") {
+        Some(branch) => branch.to_string(),
+        None => head.chars().take(7).collect(),
+    })
+}
+
+/// 未保存のファイル状態を保持する構造体
+pub(crate) struct UnsavedFile {
+    pub(crate) buffer: Rope,
+    pub(crate) saved_content: String,
+    pub(crate) cursor_line: usize,
+    pub(crate) cursor_col: usize,
+    pub(crate) scroll_offset: usize,
+    pub(crate) horizontal_scroll: usize,
+    /// ファイルを開いた時の更新日時（外部変更検知用）
+    pub(crate) modified_time: Option<SystemTime>,
+    /// 外部で変更されたフラグ
+    pub(crate) externally_modified: bool,
+    /// ブックマークした行
+    pub(crate) bookmarks: Vec<usize>,
+}
+
+/// ジャンプリストに保持する最大件数
+pub(crate) const JUMP_LIST_MAX: usize = 100;
+
+/// コマンドの種別（連続killの追記やM-yの判定用）
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum CommandKind {
+    Kill,
+    Yank,
+    Other,
+}
+
+/// 数引数（EmacsのC-u / M-数字）
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct PrefixArg {
+    /// 繰り返し回数
+    pub(crate) count: usize,
+    /// 数字が入力済みか（未入力の間はC-uのたびに4倍）
+    pub(crate) has_digits: bool,
+}
+
+/// Vimのモード
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum VimMode {
+    Normal,
+    Insert,
+    Visual,
+}
+
+/// ターミナルのカーソルの形（入力中はバー、Vimのノーマルモードなどはブロック）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    Default,
+    Block,
+    Bar,
+}
+
+impl CursorShape {
+    pub fn style(self) -> SetCursorStyle {
+        match self {
+            CursorShape::Default => SetCursorStyle::DefaultUserShape,
+            CursorShape::Block => SetCursorStyle::SteadyBlock,
+            CursorShape::Bar => SetCursorStyle::SteadyBar,
+        }
+    }
+}
+
+/// 保存済みの内容との差分表示
+pub(crate) struct DiffView {
+    pub(crate) lines: Vec<Line<'static>>,
+    pub(crate) insertions: usize,
+    pub(crate) deletions: usize,
+    pub(crate) scroll: usize,
+}
+
+impl DiffView {
+    /// 行単位の差分を計算（変更箇所の前後3行を表示し、変更された部分を強調）
+    pub(crate) fn new(old: &str, new: &str) -> Self {
+        let diff = TextDiff::from_lines(old, new);
+        let mut lines = Vec::new();
+        let mut insertions = 0;
+        let mut deletions = 0;
+
+        for group in diff.grouped_ops(3) {
+            let (Some(first), Some(last)) = (group.first(), group.last()) else {
+                continue;
+            };
+            let old_range = first.old_range().start..last.old_range().end;
+            let new_range = first.new_range().start..last.new_range().end;
+            lines.push(Line::from(Span::styled(
+                format!("@@ -{},{} +{},{} @@", old_range.start + 1, old_range.len(), new_range.start + 1, new_range.len()),
+                Style::default().fg(Color::Cyan),
+            )));
+
+            for op in &group {
+                for change in diff.iter_inline_changes(op) {
+                    let (sign, color) = match change.tag() {
+                        ChangeTag::Delete => {
+                            deletions += 1;
+                            ("-", Some(Color::Red))
+                        }
+                        ChangeTag::Insert => {
+                            insertions += 1;
+                            ("+", Some(Color::Green))
+                        }
+                        ChangeTag::Equal => (" ", None),
+                    };
+                    let line_number = |idx: Option<usize>| idx.map_or(String::new(), |i| (i + 1).to_string());
+                    let mut spans = vec![Span::styled(
+                        format!("{:>5} {:>5} {}", line_number(change.old_index()), line_number(change.new_index()), sign),
+                        Style::default().fg(Color::DarkGray),
+                    )];
+                    let style = color.map_or(Style::default(), |c| Style::default().fg(c));
+                    for (emphasized, value) in change.iter_strings_lossy() {
+                        let text = value.trim_end_matches(['\n', '\r']).replace('\t', "    ");
+                        if text.is_empty() {
+                            continue;
+                        }
+                        // 行内で変更された部分は反転表示
+                        let style = if emphasized {
+                            style.add_modifier(Modifier::REVERSED)
+                        } else {
+                            style
+                        };
+                        spans.push(Span::styled(text, style));
+                    }
+                    lines.push(Line::from(spans));
+                }
+            }
+        }
+
+        if lines.is_empty() {
+            lines.push(Line::from("No changes"));
+        }
+        Self { lines, insertions, deletions, scroll: 0 }
+    }
+
+    pub(crate) fn scroll_by(&mut self, delta: isize, visible_height: usize) {
+        let max_scroll = self.lines.len().saturating_sub(visible_height);
+        self.scroll = self.scroll.saturating_add_signed(delta).min(max_scroll);
+    }
+}
+
+/// 絞り込み一覧（コマンドパレット・最近のファイル）の入力状態
+#[derive(Default)]
+pub(crate) struct FilterList {
+    pub(crate) query: String,
+    pub(crate) selected: usize,
+}
+
+/// 絞り込み一覧でのキー入力の結果
+pub(crate) enum FilterListInput {
+    Continue,
+    Cancel,
+    Submit,
+}
+
+impl FilterList {
+    /// キー入力を処理（item_countは絞り込み後の件数）
+    pub fn handle_key(&mut self, key: &KeyEvent, item_count: usize) -> FilterListInput {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return FilterListInput::Cancel,
+            KeyCode::Char('g') if ctrl => return FilterListInput::Cancel,
+            KeyCode::Enter => return FilterListInput::Submit,
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Char('p') if ctrl => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(item_count.saturating_sub(1)),
+            KeyCode::Char('n') if ctrl => self.selected = (self.selected + 1).min(item_count.saturating_sub(1)),
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.selected = 0;
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.selected = 0;
+            }
+            _ => {}
+        }
+        FilterListInput::Continue
+    }
+
+    /// 入力した語をすべて含むか（大文字小文字は区別しない）
+    pub(crate) fn matches(&self, text: &str) -> bool {
+        let text = text.to_lowercase();
+        self.query.to_lowercase().split_whitespace().all(|word| text.contains(word))
+    }
+}
+
+/// 入力補完の候補一覧
+pub(crate) struct Completion {
+    /// 補完中の単語の開始位置（文字インデックス）
+    pub(crate) start: usize,
+    /// 入力済みの部分
+    pub(crate) prefix: String,
+    pub(crate) candidates: Vec<String>,
+    pub(crate) selected: usize,
+}
+
+/// スペルの修正候補の一覧
+pub(crate) struct SpellSuggestion {
+    /// 修正する単語の範囲（文字インデックス）
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) candidates: Vec<String>,
+    pub(crate) selected: usize,
+}
+
+/// 画面下部の入力欄の用途
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum PromptKind {
+    GoToLine,
+    SaveAs,
+    AddFolder,
+}
+
+impl PromptKind {
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            PromptKind::GoToLine => "Go to line: ",
+            PromptKind::SaveAs => "Save as: ",
+            PromptKind::AddFolder => "Add folder: ",
+        }
+    }
+}
+
+/// 画面下部の入力欄
+pub(crate) struct Prompt {
+    pub(crate) kind: PromptKind,
+    pub(crate) input: String,
+}
+
+/// ワークスペースのルートフォルダ
+pub(crate) struct WorkspaceRoot {
+    pub(crate) path: PathBuf,
+    /// サイドバーで表示中のディレクトリ（path以下）
+    pub(crate) current_dir: PathBuf,
+    pub(crate) entries: Vec<PathBuf>,
+    /// サイドバーで折りたたまれているか（複数ルートの場合のみ）
+    pub(crate) collapsed: bool,
+}
+
+impl WorkspaceRoot {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self {
+            entries: App::read_dir(&path),
+            current_dir: path.clone(),
+            path,
+            collapsed: false,
+        }
+    }
+
+    pub(crate) fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "/".to_string())
+    }
+}
+
+/// サイドバーの1行
+#[derive(Clone)]
+pub(crate) enum SidebarItem {
+    /// ルートの見出し（複数ルートの場合のみ）
+    Root(usize),
+    /// 親ディレクトリ（".."）
+    Parent(usize),
+    Entry(usize, PathBuf),
+}
+
+impl SidebarItem {
+    /// 行が属するルートの位置
+    pub(crate) fn root(&self) -> usize {
+        match self {
+            SidebarItem::Root(i) | SidebarItem::Parent(i) | SidebarItem::Entry(i, _) => *i,
+        }
+    }
+}
+
+pub struct App {
+    // ワークスペースのルートフォルダとサイドバーで操作中のルート
+    pub(crate) roots: Vec<WorkspaceRoot>,
+    pub(crate) active_root: usize,
+    // サイドバーに表示するファイルサイズ（show_file_sizesが有効な場合のみ）
+    pub(crate) show_file_sizes: bool,
+    pub(crate) entry_sizes: HashMap<PathBuf, u64>,
+    // サイドバーのシンボリックリンクのリンク先
+    pub(crate) entry_links: HashMap<PathBuf, PathBuf>,
+    // フォーカス中のエディタペイン
+    pub(crate) pane: EditorPane,
+    // 分割表示中のもう一方のペインと分割方向
+    pub(crate) other_pane: Option<EditorPane>,
+    pub(crate) split_direction: Direction,
+    // フォーカス中のペインの位置（分割の左/上が0）
+    pub(crate) focused_pane: usize,
+    pub(crate) sidebar_area: Rect,
+    // サイドバーの表示状態と幅（%）
+    pub(crate) sidebar_visible: bool,
+    pub(crate) sidebar_width_percent: u16,
+    // サイドバーの境界をドラッグして幅を変更中か
+    pub(crate) sidebar_resizing: bool,
+    // ミニマップを表示するか、表示した領域（表示していなければ空）、ドラッグでスクロール中か
+    pub(crate) minimap: bool,
+    pub(crate) minimap_area: Rect,
+    pub(crate) minimap_dragging: bool,
+    pub(crate) sidebar_scroll: usize,
+    pub(crate) sidebar_scroll_x: usize,
+    // マウスホイール1回のスクロール量（行数、桁数）
+    pub(crate) scroll_lines: usize,
+    pub(crate) scroll_columns: usize,
+    pub(crate) scroll_margin: usize,
+    // 対応するファイルへの切り替え規則（長い末尾から順）
+    pub(crate) counterpart_rules: Vec<(String, Vec<String>)>,
+    // サイドバーのキーボード操作（フォーカス中の絞り込み文字列と選択位置）
+    pub(crate) sidebar_focused: bool,
+    pub(crate) sidebar_filter: String,
+    pub(crate) sidebar_selected: usize,
+    pub(crate) needs_clear: bool,
+    pub(crate) syntax: SyntaxHighlighter,
+    // 長い行を折り返して表示するか
+    pub(crate) wrap_lines: bool,
+    // 縦線のガイドを表示する桁
+    pub(crate) wrap_guide_column: Option<usize>,
+    // HTMLの閉じタグやMarkdownのリストの続きを自動で挿入するか
+    pub(crate) smart_insert: bool,
+    // 保存時に実行するフォーマッタ（言語 → コマンド）
+    pub(crate) format_on_save: HashMap<Language, String>,
+    // 画像表示用
+    pub(crate) picker: Picker,
+    pub(crate) image_state: Option<ThreadProtocol>,
+    // デコード中ならデコードを要求した時刻（スピナー表示用）
+    pub(crate) image_loading: Option<Instant>,
+    // 最新のデコード要求の世代番号（古い要求の結果は捨てる）
+    pub(crate) image_generation: u64,
+    // 画像の拡大・表示位置
+    pub(crate) image_view: ImageView,
+    // 画像のピクセルサイズ（デコード完了後に設定）とファイルサイズ
+    pub(crate) image_dimensions: Option<(u32, u32)>,
+    pub(crate) image_file_size: Option<u64>,
+    // 画像リサイズ用スレッド通信
+    pub(crate) image_tx: Sender<ResizeRequest>,
+    pub(crate) image_rx: Receiver<StatefulProtocol>,
+    // 最後に要求した画像の表示先（拡大中か、表示領域）と変わった時刻
+    pub(crate) image_resize_target: Option<(bool, Rect, Instant)>,
+    // 画像デコード用スレッド通信
+    pub(crate) decode_tx: Sender<DecodeRequest>,
+    pub(crate) decode_rx: Receiver<DecodeResult>,
+    // 未保存ファイルの保持（タブ機能）
+    pub(crate) unsaved_files: HashMap<PathBuf, UnsavedFile>,
+    // 保存済みのタブのブックマーク（未保存のタブはUnsavedFileに保持）
+    pub(crate) file_bookmarks: HashMap<PathBuf, Vec<usize>>,
+    // 組み込みターミナル（起動後は非表示でも動き続ける）
+    pub(crate) terminal: Option<TerminalPane>,
+    pub(crate) terminal_visible: bool,
+    pub(crate) terminal_focused: bool,
+    pub(crate) terminal_area: Rect,
+    // ディレクトリごとのgitディレクトリ（リポジトリ外ならNone）
+    pub(crate) git_dirs: HashMap<PathBuf, Option<PathBuf>>,
+    // タブ管理
+    pub(crate) tabs: Vec<PathBuf>,
+    pub(crate) tab_area: Rect,
+    // 確認ダイアログ
+    pub(crate) confirm_dialog: Option<ConfirmAction>,
+    // 上書きの確認中の保存先
+    pub(crate) pending_save_as: Option<PathBuf>,
+    // 検索機能
+    pub(crate) search_mode: bool,
+    pub(crate) search_query: String,
+    pub(crate) search_matches: Vec<(usize, usize)>,  // (line, col)
+    pub(crate) search_index: usize,
+    // 大文字・小文字を区別しないか、最後のマッチから最初に戻るか
+    pub(crate) search_case_insensitive: bool,
+    pub(crate) search_wrap: bool,
+    // テキスト選択
+    pub(crate) selection: Option<Selection>,
+    pub(crate) is_selecting: bool,
+    // コピーボタン表示位置（画面座標）
+    pub(crate) copy_button_area: Option<Rect>,
+    // 入力中の数引数
+    pub(crate) prefix_arg: Option<PrefixArg>,
+    // マーク設定中か（設定中は選択範囲がカーソルに追従する）
+    pub(crate) mark_active: bool,
+    // キルリング（新しいものが末尾）
+    pub(crate) kill_ring: Vec<String>,
+    // M-yで巡回中のキルリング位置
+    pub(crate) kill_ring_index: usize,
+    // 直前のヤンクで挿入した範囲（文字インデックス）
+    pub(crate) last_yank: Option<(usize, usize)>,
+    // 直前のコマンドと実行中のコマンド
+    pub(crate) last_command: CommandKind,
+    pub(crate) this_command: CommandKind,
+    // キーバインド
+    pub(crate) keymap: Keymap,
+    // Vimのモード（無効時はNone）
+    pub(crate) vim_mode: Option<VimMode>,
+    // Vimの入力途中のオペレータと回数（dd の1文字目）
+    pub(crate) vim_pending: Option<(char, usize)>,
+    // Vimのコマンドライン入力（:w など）
+    pub(crate) vim_command: Option<String>,
+    // 入力途中の複数キー（C-x C-p など）
+    pub(crate) pending_keys: Vec<KeySpec>,
+    // コマンドパレット
+    pub(crate) command_palette: Option<FilterList>,
+    /// 最近開いたファイル（新しい順）
+    pub(crate) recent_files: Vec<PathBuf>,
+    /// 最近のファイル一覧（表示中のみSome）
+    pub(crate) recent_list: Option<FilterList>,
+    /// ワークスペースのファイル検索（表示中のみSome、閉じると列挙も止まる）
+    pub(crate) file_finder: Option<FileFinder>,
+    /// 入力補完の候補（表示中のみSome）
+    pub(crate) completion: Option<Completion>,
+    /// スペルチェックの辞書（無効か見つからなければNone）
+    pub(crate) spell_dictionary: Option<Dictionary>,
+    /// スペルの修正候補（表示中のみSome）
+    pub(crate) spell_suggestion: Option<SpellSuggestion>,
+    /// ステータスバーに表示するメッセージと表示を始めた時刻（一定時間で消える）
+    pub(crate) status_message: Option<(String, Instant)>,
+    /// 設定ファイルで指定された配色
+    pub(crate) colors: UiColors,
+    // 画面下部の入力欄（行ジャンプなど）
+    pub(crate) prompt: Option<Prompt>,
+    // 保存済みの内容との差分表示
+    pub(crate) diff_view: Option<DiffView>,
+    // ジャンプリスト（ファイル、行、列）と現在位置
+    pub(crate) jump_list: Vec<(PathBuf, usize, usize)>,
+    pub(crate) jump_index: usize,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum ConfirmAction {
+    Quit,
+    CloseTab,
+    Overwrite,
+}
+
+impl App {
+    pub fn new(initial_paths: Vec<PathBuf>, initial_position: Option<(usize, usize)>) -> Self {
+        // 初期パスの処理
+        let mut initial_paths = initial_paths.into_iter();
+        let (root_dir, initial_file) = if let Some(path) = initial_paths.next() {
+            let abs_path = if path.is_absolute() {
+                path
+            } else {
+                env::current_dir().unwrap_or_default().join(path)
+            };
+
+            if abs_path.is_file() {
+                // ファイルの場合：親ディレクトリを開き、ファイルを展開
+                let parent = abs_path.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+                (parent, Some(abs_path))
+            } else if abs_path.is_dir() {
+                // ディレクトリの場合：そのディレクトリを開く
+                (abs_path, None)
+            } else {
+                // 存在しない場合：カレントディレクトリ
+                let cwd = env::current_dir().unwrap_or_default();
+                (cwd, None)
+            }
+        } else {
+            let cwd = env::current_dir().unwrap_or_default();
+            (cwd, None)
+        };
+
+        // 2つ目以降のディレクトリはワークスペースのルートに追加
+        let mut roots = vec![root_dir];
+        for path in initial_paths {
+            let path = env::current_dir().unwrap_or_default().join(path);
+            if path.is_dir() && !roots.contains(&path) {
+                roots.push(path);
+            }
+        }
+        let mut config = Config::load();
+        // 設定ファイルで幅を指定していなければドラッグで変更した幅を使う
+        if config.sidebar_width_percent.is_none() {
+            config.sidebar_width_percent = load_sidebar_width();
+        }
+        let picker = Picker::from_query_stdio()
+            .unwrap_or_else(|_| Picker::from_fontsize((8, 12)));
+        // 背景色の問い合わせも画像の問い合わせと同じく、イベントを読み始める前に行う
+        let colors = UiColors::from_config(&config.colors);
+        let spell_check = config.spell_check;
+        let spell_dictionary = config.spell_dictionary.clone();
+
+        let mut app = Self::with_config(roots, config, picker, colors);
+        app.recent_files = load_recent_files();
+        if spell_check {
+            app.spell_dictionary = Dictionary::load(spell_dictionary.as_deref(), personal_dictionary_path().as_deref());
+            if app.spell_dictionary.is_none() {
+                app.set_status_message("No dictionary found for spell checking");
+            }
+        }
+
+        // 初期ファイルがあれば開く
+        if let Some(file_path) = initial_file {
+            app.open_file(&file_path);
+            // "+行" や "ファイル:行:列" で指定された位置へ移動
+            if let Some((line, col)) = initial_position.filter(|_| !app.pane.is_image_mode) {
+                app.go_to_line(line);
+                app.pane.cursor_col = col.saturating_sub(1);
+                app.clamp_cursor_col();
+            }
+        }
+
+        app
+    }
+
+    /// 端末への問い合わせや設定ファイルの読み込みをせずに作成（ルートが空なら現在のディレクトリを開く）
+    pub fn with_config(roots: Vec<PathBuf>, config: Config, picker: Picker, colors: UiColors) -> Self {
+        let mut roots: Vec<WorkspaceRoot> = roots.into_iter().map(WorkspaceRoot::new).collect();
+        if roots.is_empty() {
+            roots.push(WorkspaceRoot::new(env::current_dir().unwrap_or_default()));
+        }
+
+        // 画像リサイズ用のワーカースレッドを起動
+        let (tx_worker, rx_worker) = mpsc::channel::<ResizeRequest>();
+        let (tx_main, rx_main) = mpsc::channel::<StatefulProtocol>();
+        std::thread::spawn(move || {
+            while let Ok(mut request) = rx_worker.recv() {
+                // 溜まっている要求は最新のものだけ処理し、古い要求は捨てる
+                while let Ok(newer) = rx_worker.try_recv() {
+                    request = newer;
+                }
+                let (mut protocol, resize, area) = request;
+                protocol.resize_encode(&resize, protocol.background_color(), area);
+                let _ = tx_main.send(protocol);
+            }
+        });
+
+        // 画像デコード用のワーカースレッドを起動
+        let (decode_tx, decode_rx_worker) = mpsc::channel::<DecodeRequest>();
+        let (decode_tx_main, decode_rx) = mpsc::channel::<DecodeResult>();
+        std::thread::spawn(move || {
+            // 拡大・移動のたびに再デコードしないよう、直前の画像を保持
+            let mut decoded: Option<(PathBuf, image::DynamicImage)> = None;
+            while let Ok(mut request) = decode_rx_worker.recv() {
+                // 溜まっている要求は最新のものだけ処理し、古い要求は捨てる
+                while let Ok(newer) = decode_rx_worker.try_recv() {
+                    request = newer;
+                }
+                let (generation, path, view, picker, resize_tx) = request;
+                if decoded.as_ref().map(|(p, _)| p) != Some(&path) {
+                    decoded = decode_image(&path).map(|img| (path.clone(), img));
+                }
+                if let Some((_, full_img)) = &decoded {
+                    // 表示範囲で切り抜いてから、大きすぎる画像は縮小
+                    let dyn_img = view.crop(full_img);
+                    let (cols, rows) = crossterm::terminal::size().unwrap_or((80, 24));
+                    let max_width = (cols as u32) * 10;
+                    let max_height = (rows as u32) * 20;
+                    let img = if dyn_img.width() > max_width || dyn_img.height() > max_height {
+                        dyn_img.resize(max_width, max_height, image::imageops::FilterType::Nearest)
+                    } else {
+                        dyn_img
+                    };
+                    let protocol = picker.new_resize_protocol(img);
+                    let thread_protocol = ThreadProtocol::new(resize_tx, protocol);
+                    let _ = decode_tx_main.send((generation, thread_protocol, (full_img.width(), full_img.height())));
+                }
+            }
+        });
+
+        let mut app = App {
+            roots,
+            active_root: 0,
+            entry_sizes: HashMap::new(),
+            show_file_sizes: config.show_file_sizes,
+            entry_links: HashMap::new(),
+            pane: EditorPane::default(),
+            other_pane: None,
+            split_direction: Direction::Horizontal,
+            focused_pane: 0,
+            sidebar_area: Rect::default(),
+            sidebar_visible: true,
+            sidebar_width_percent: config.sidebar_width_percent
+                .unwrap_or(DEFAULT_SIDEBAR_WIDTH_PERCENT)
+                .clamp(SIDEBAR_WIDTH_MIN_PERCENT, SIDEBAR_WIDTH_MAX_PERCENT),
+            sidebar_resizing: false,
+            minimap: config.minimap,
+            minimap_area: Rect::default(),
+            minimap_dragging: false,
+            sidebar_scroll: 0,
+            sidebar_scroll_x: 0,
+            scroll_lines: config.scroll_lines.unwrap_or(DEFAULT_SCROLL_LINES).max(1),
+            scroll_columns: config.scroll_columns.unwrap_or(DEFAULT_SCROLL_COLUMNS).max(1),
+            scroll_margin: config.scroll_margin.unwrap_or(DEFAULT_SCROLL_MARGIN),
+            counterpart_rules: counterpart_rules(&config.counterparts),
+            sidebar_focused: false,
+            sidebar_filter: String::new(),
+            sidebar_selected: 0,
+            needs_clear: false,
+            syntax: SyntaxHighlighter::new(&config.extensions),
+            wrap_lines: config.wrap_lines,
+            wrap_guide_column: config.wrap_guide_column.filter(|&column| column > 0),
+            smart_insert: config.smart_insert.unwrap_or(true),
+            format_on_save: config.format_on_save.iter()
+                .filter_map(|(lang, command)| {
+                    SyntaxHighlighter::parse_language(lang).map(|l| (l, command.clone()))
+                })
+                .collect(),
+            picker,
+            image_state: None,
+            image_loading: None,
+            image_generation: 0,
+            image_view: ImageView::default(),
+            image_dimensions: None,
+            image_file_size: None,
+            image_tx: tx_worker,
+            image_rx: rx_main,
+            image_resize_target: None,
+            decode_tx,
+            decode_rx,
+            unsaved_files: HashMap::new(),
+            file_bookmarks: HashMap::new(),
+            terminal: None,
+            terminal_visible: false,
+            terminal_focused: false,
+            terminal_area: Rect::default(),
+            git_dirs: HashMap::new(),
+            tabs: Vec::new(),
+            tab_area: Rect::default(),
+            confirm_dialog: None,
+            pending_save_as: None,
+            search_mode: false,
+            search_query: String::new(),
+            search_matches: Vec::new(),
+            search_case_insensitive: false,
+            search_wrap: true,
+            search_index: 0,
+            selection: None,
+            is_selecting: false,
+            copy_button_area: None,
+            prefix_arg: None,
+            mark_active: false,
+            kill_ring: Vec::new(),
+            kill_ring_index: 0,
+            last_yank: None,
+            last_command: CommandKind::Other,
+            this_command: CommandKind::Other,
+            keymap: Keymap::new(&config.keybindings),
+            vim_mode: config.vim_mode.then_some(VimMode::Normal),
+            vim_pending: None,
+            vim_command: None,
+            pending_keys: Vec::new(),
+            command_palette: None,
+            recent_files: Vec::new(),
+            recent_list: None,
+            file_finder: None,
+            completion: None,
+            spell_dictionary: None,
+            spell_suggestion: None,
+            status_message: None,
+            colors,
+            prompt: None,
+            diff_view: None,
+            jump_list: Vec::new(),
+            jump_index: 0,
+        };
+
+        app.load_entry_info();
+        let root = app.roots[0].path.clone();
+        app.pane.git_branch = app.get_git_branch(&root);
+        app
+    }
+
+    /// 未保存のタブがあるかチェック
+    pub(crate) fn has_unsaved_tabs(&self) -> bool {
+        // 現在のファイルが未保存
+        if self.is_unsaved() {
+            return true;
+        }
+        // 他のタブ・ペインに未保存がある
+        !self.unsaved_files.is_empty()
+            || self.other_pane.as_ref().is_some_and(|p| p.buffer != p.saved_content)
+    }
+
+    pub(crate) fn read_dir(path: &PathBuf) -> Vec<PathBuf> {
+        let mut entries: Vec<PathBuf> = fs::read_dir(path)
+            .map(|rd| rd.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+            .unwrap_or_default();
+        entries.sort();
+        entries
+    }
+
+    /// ファイルのサイズを取得（ディレクトリは含めない）
+    pub(crate) fn read_file_sizes(entries: &[PathBuf]) -> HashMap<PathBuf, u64> {
+        entries.iter()
+            .filter_map(|path| {
+                let metadata = fs::metadata(path).ok()?;
+                metadata.is_file().then(|| (path.clone(), metadata.len()))
+            })
+            .collect()
+    }
+
+    /// シンボリックリンクのリンク先を取得（is_dir()などはリンクを辿るので区別して表示する）
+    pub(crate) fn read_link_targets(entries: &[PathBuf]) -> HashMap<PathBuf, PathBuf> {
+        entries.iter()
+            .filter(|path| fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink()))
+            .filter_map(|path| fs::read_link(path).ok().map(|target| (path.clone(), target)))
+            .collect()
+    }
+
+    /// サイドバーのエントリのリンク先（とファイルサイズ）を読み込む
+    pub(crate) fn load_entry_info(&mut self) {
+        let entries: Vec<PathBuf> = self.roots.iter().flat_map(|r| r.entries.iter().cloned()).collect();
+        self.entry_links = Self::read_link_targets(&entries);
+        if self.show_file_sizes {
+            self.entry_sizes = Self::read_file_sizes(&entries);
+        }
+    }
+
+    /// サイドバーで操作中のディレクトリ
+    pub(crate) fn current_dir(&self) -> &Path {
+        &self.roots[self.active_root].current_dir
+    }
+
+    /// ディレクトリのあるリポジトリのブランチ名（gitディレクトリの場所はキャッシュし、HEADは毎回読む）
+    pub(crate) fn get_git_branch(&mut self, dir: &Path) -> Option<String> {
+        let git_dir = self.git_dirs
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| find_git_dir(dir))
+            .clone()?;
+        read_git_branch(&git_dir)
+    }
+
+    /// ファイルの更新日時を取得
+    pub(crate) fn get_file_modified_time(path: &PathBuf) -> Option<SystemTime> {
+        fs::metadata(path).ok().and_then(|m| m.modified().ok())
+    }
+
+    pub fn open_file(&mut self, path: &PathBuf) {
+        if path.is_file() {
+            self.diff_view = None;
+            self.completion = None;
+            self.spell_suggestion = None;
+            // 同じファイルを表示しているペインに最新の内容を反映
+            self.sync_other_pane();
+            // 現在のファイルの状態を保存
+            if let Some(current_path) = &self.pane.file_path.clone() {
+                if !self.pane.is_image_mode {
+                    let shown_in_other_pane = self.other_pane.as_ref()
+                        .is_some_and(|p| p.file_path.as_ref() == Some(current_path));
+                    let bookmarks = std::mem::take(&mut self.pane.bookmarks);
+                    if self.is_unsaved() && !shown_in_other_pane {
+                        // 未保存なら保持（もう一方のペインで表示中ならそちらが保持）
+                        self.unsaved_files.insert(current_path.clone(), UnsavedFile {
+                            buffer: self.pane.buffer.clone(),
+                            saved_content: self.pane.saved_content.clone(),
+                            cursor_line: self.pane.cursor_line,
+                            cursor_col: self.pane.cursor_col,
+                            scroll_offset: self.pane.scroll_offset,
+                            horizontal_scroll: self.pane.horizontal_scroll,
+                            modified_time: self.pane.file_modified_time,
+                            externally_modified: false,
+                            bookmarks,
+                        });
+                    } else {
+                        // 保存済み（またはペインが保持）ならメモリから削除
+                        self.unsaved_files.remove(current_path);
+                        if !shown_in_other_pane && !bookmarks.is_empty() {
+                            self.file_bookmarks.insert(current_path.clone(), bookmarks);
+                        }
+                    }
+                }
+            }
+
+            self.pane.file_path = Some(path.clone());
+            self.pane.goal_col = None;
+            self.pane.folds.clear();
+            self.pane.cursors.clear();
+            self.add_recent_file(path);
+            self.pane.git_branch = path.parent().and_then(|dir| self.get_git_branch(dir));
+            self.needs_clear = true;
+
+            // 現在のディスク上のファイルの更新日時を取得
+            let current_disk_modified = Self::get_file_modified_time(path);
+
+            if is_image_file(path) {
+                // 画像ファイルの場合 - 非同期でデコード
+                self.image_view = ImageView::default();
+                self.image_dimensions = None;
+                self.image_file_size = fs::metadata(path).ok().map(|m| m.len());
+                self.request_image_decode(path.clone());
+                self.image_state = None;
+                self.pane.is_image_mode = true;
+                self.image_loading = Some(Instant::now());
+                self.pane.bookmarks.clear();
+                // テキストバッファはクリア
+                self.pane.buffer = Rope::new();
+                self.pane.saved_content.clear();
+                self.pane.file_modified_time = current_disk_modified;
+                self.pane.current_language = None;
+                self.pane.cursor_line = 0;
+                self.pane.cursor_col = 0;
+                self.pane.scroll_offset = 0;
+                self.pane.horizontal_scroll = 0;
+            } else if let Some(other) = self.other_pane.as_ref()
+                .filter(|p| p.file_path.as_ref() == Some(path) && !p.is_image_mode)
+            {
+                // もう一方のペインで開いている場合は内容を共有
+                self.pane.buffer = other.buffer.clone();
+                self.pane.saved_content = other.saved_content.clone();
+                self.pane.file_modified_time = other.file_modified_time;
+                self.pane.current_language = other.current_language;
+                self.pane.cursor_line = other.cursor_line;
+                self.pane.cursor_col = other.cursor_col;
+                self.pane.scroll_offset = other.scroll_offset;
+                self.pane.horizontal_scroll = other.horizontal_scroll;
+                self.pane.bookmarks = other.bookmarks.clone();
+                self.unsaved_files.remove(path);
+                self.image_state = None;
+                self.pane.is_image_mode = false;
+                self.image_loading = None;
+            } else if let Some(mut unsaved) = self.unsaved_files.remove(path) {
+                // 未保存の状態があれば復元
+                // 外部で変更されたか確認
+                let was_externally_modified = match (unsaved.modified_time, current_disk_modified) {
+                    (Some(saved_time), Some(disk_time)) => disk_time > saved_time,
+                    _ => false,
+                };
+
+                if was_externally_modified {
+                    // 外部変更があった場合、フラグを立てて新しい内容をsaved_contentに
+                    let new_content = fs::read_to_string(path).unwrap_or_else(|_| String::new());
+                    unsaved.saved_content = new_content;
+                    unsaved.externally_modified = true;
+                }
+
+                self.pane.buffer = unsaved.buffer;
+                self.pane.saved_content = unsaved.saved_content;
+                self.pane.cursor_line = unsaved.cursor_line;
+                self.pane.cursor_col = unsaved.cursor_col;
+                self.pane.scroll_offset = unsaved.scroll_offset;
+                self.pane.horizontal_scroll = unsaved.horizontal_scroll;
+                self.pane.bookmarks = unsaved.bookmarks;
+                self.pane.file_modified_time = current_disk_modified;
+                self.pane.current_language = self.syntax.detect_language(path);
+                self.image_state = None;
+                self.pane.is_image_mode = false;
+                self.image_loading = None;
+            } else {
+                // ディスクから読み込み
+                let content = fs::read_to_string(path).unwrap_or_else(|_| String::new());
+                self.pane.buffer = Rope::from_str(&content);
+                self.pane.saved_content = content;
+                self.pane.file_modified_time = current_disk_modified;
+                self.pane.current_language = self.syntax.detect_language(path);
+                self.image_state = None;
+                self.pane.is_image_mode = false;
+                self.image_loading = None;
+                self.pane.cursor_line = 0;
+                self.pane.cursor_col = 0;
+                self.pane.scroll_offset = 0;
+                self.pane.horizontal_scroll = 0;
+                self.pane.bookmarks = self.file_bookmarks.remove(path).unwrap_or_default();
+            }
+
+            // ログなどANSIの色付きの出力はエスケープシーケンスを解釈して表示
+            self.pane.ansi_colors = !self.pane.is_image_mode && is_ansi_colored(path, &self.pane.buffer);
+
+            self.pane.source_cache.clear();
+            self.pane.highlight_cache = None;
+            self.pane.line_offsets.clear();
+            self.pane.max_line_width = 0;
+            self.pane.buffer_dirty = true;
+        }
+    }
+
+    /// ステータスバーにメッセージを表示
+    pub(crate) fn set_status_message(&mut self, message: impl Into<String>) {
+        self.status_message = Some((message.into(), Instant::now()));
+    }
+
+    /// 表示時間を過ぎたステータスメッセージを消す
+    pub(crate) fn expire_status_message(&mut self) {
+        if self.status_message.as_ref().is_some_and(|(_, shown_at)| shown_at.elapsed() >= STATUS_MESSAGE_DURATION) {
+            self.status_message = None;
+        }
+    }
+
+    /// 現在のファイルを保存し、失敗した場合はステータスバーにエラーを表示（成功したらtrue）
+    pub fn save_current_file(&mut self) -> bool {
+        match self.save_file() {
+            Ok(()) => true,
+            Err(e) => {
+                let name = self.pane.file_path.as_ref()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default();
+                self.set_status_message(format!("Failed to save {}: {}", name, e));
+                false
+            }
+        }
+    }
+
+    pub(crate) fn save_file(&mut self) -> io::Result<()> {
+        self.run_format_on_save();
+        if let Some(path) = &self.pane.file_path {
+            let content = self.pane.buffer.to_string();
+            fs::write(path, &content)?;
+            self.pane.saved_content = content;
+            // 保存後の更新日時を記録
+            self.pane.file_modified_time = Self::get_file_modified_time(path);
+        }
+        Ok(())
+    }
+
+    /// 別名で保存する入力欄を表示（現在のディレクトリ内のファイルなら名前を入力済みにする）
+    pub(crate) fn start_save_as(&mut self) {
+        if self.pane.is_image_mode {
+            return;
+        }
+        let input = self.pane.file_path.as_ref()
+            .and_then(|p| p.strip_prefix(self.current_dir()).ok())
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+        self.prompt = Some(Prompt { kind: PromptKind::SaveAs, input });
+    }
+
+    /// 別名で保存（相対パスはサイドバーのディレクトリ基準）
+    pub(crate) fn save_as(&mut self, input: &str) {
+        if input.is_empty() || self.pane.is_image_mode {
+            return;
+        }
+        let path = self.current_dir().join(input);
+        if path.is_dir() {
+            self.set_status_message(format!("{} is a directory", path.display()));
+            return;
+        }
+        if self.pane.file_path.as_ref() != Some(&path) {
+            // 別のタブやペインの未保存の変更は上書きしない
+            let other_unsaved = self.other_pane.as_ref().is_some_and(|other| {
+                other.file_path.as_ref() == Some(&path) && other.buffer != other.saved_content
+            });
+            if self.unsaved_files.contains_key(&path) || other_unsaved {
+                self.set_status_message(format!("{} has unsaved changes in another tab", path.display()));
+                return;
+            }
+            // 既存のファイルは確認してから上書き
+            if path.exists() {
+                self.pending_save_as = Some(path);
+                self.confirm_dialog = Some(ConfirmAction::Overwrite);
+                return;
+            }
+        }
+        self.write_as(path);
+    }
+
+    /// 現在のバッファをpathに保存し、以後はpathのファイルとして編集
+    pub(crate) fn write_as(&mut self, path: PathBuf) {
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+
+        let old_path = self.pane.file_path.replace(path.clone());
+        let old_language = self.pane.current_language;
+        self.pane.current_language = self.syntax.detect_language(&path);
+        if let Err(e) = self.save_file() {
+            self.pane.file_path = old_path;
+            self.pane.current_language = old_language;
+            self.set_status_message(format!("Failed to save {}: {}", path.display(), e));
+            return;
+        }
+        // 言語が変わるとハイライトが変わるのでキャッシュを作り直す
+        self.pane.buffer_dirty = true;
+        self.pane.git_branch = path.parent().and_then(|dir| self.get_git_branch(dir));
+
+        // 元のタブを新しいパスに置き換え
+        self.tabs.retain(|p| p != &path);
+        match old_path.and_then(|old| self.tabs.iter().position(|p| *p == old)) {
+            Some(index) => self.tabs[index] = path.clone(),
+            None => self.tabs.push(path.clone()),
+        }
+        self.add_recent_file(&path);
+        self.refresh_directory();
+    }
+
+    /// 未保存のタブをすべて保存
+    pub(crate) fn save_all(&mut self) -> io::Result<()> {
+        if self.is_unsaved() {
+            self.save_file()?;
+        }
+        self.sync_other_pane();
+        if let Some(other) = self.other_pane.as_mut() {
+            if let Some(path) = &other.file_path {
+                if !other.is_image_mode && other.buffer != other.saved_content {
+                    let content = other.buffer.to_string();
+                    fs::write(path, &content)?;
+                    other.saved_content = content;
+                    other.file_modified_time = Self::get_file_modified_time(path);
+                }
+            }
+        }
+        let paths: Vec<PathBuf> = self.unsaved_files.keys().cloned().collect();
+        for path in paths {
+            if let Some(file) = self.unsaved_files.get(&path) {
+                fs::write(&path, file.buffer.to_string())?;
+                self.unsaved_files.remove(&path);
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn is_unsaved(&self) -> bool {
+        self.pane.buffer != self.pane.saved_content
+    }
+
+    /// 現在のファイルをタブに追加（まだなければ）
+    pub(crate) fn add_to_tabs(&mut self) {
+        if let Some(path) = &self.pane.file_path {
+            if !self.tabs.contains(path) {
+                self.tabs.push(path.clone());
+            }
+        }
+    }
+
+    /// 次のタブに切り替え
+    pub(crate) fn next_tab(&mut self) {
+        if self.tabs.len() <= 1 {
+            return;
+        }
+        if let Some(current) = &self.pane.file_path {
+            if let Some(idx) = self.tabs.iter().position(|p| p == current) {
+                let next_idx = (idx + 1) % self.tabs.len();
+                let next_path = self.tabs[next_idx].clone();
+                self.record_jump();
+                self.open_file(&next_path);
+            }
+        }
+    }
+
+    /// 前のタブに切り替え
+    pub(crate) fn prev_tab(&mut self) {
+        if self.tabs.len() <= 1 {
+            return;
+        }
+        if let Some(current) = &self.pane.file_path {
+            if let Some(idx) = self.tabs.iter().position(|p| p == current) {
+                let prev_idx = if idx == 0 { self.tabs.len() - 1 } else { idx - 1 };
+                let prev_path = self.tabs[prev_idx].clone();
+                self.record_jump();
+                self.open_file(&prev_path);
+            }
+        }
+    }
+
+    /// タブバーのクリック処理
+    pub(crate) fn handle_tab_click(&mut self, x: u16, y: u16) {
+        if !self.tabs.is_empty()
+            && y == self.tab_area.y
+            && x >= self.tab_area.x
+            && x < self.tab_area.x + self.tab_area.width
+        {
+            // クリック位置からタブを特定
+            let mut current_x = self.tab_area.x;
+            for path in &self.tabs.clone() {
+                let file_name = path.file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "New".to_string());
+
+                let is_unsaved = self.is_path_unsaved(path);
+
+                let unsaved_mark = if is_unsaved { "*" } else { "" };
+                let tab_text = format!(" {}{} ", file_name, unsaved_mark);
+                let tab_len = tab_text.len() as u16;
+
+                if x >= current_x && x < current_x + tab_len {
+                    // このタブがクリックされた
+                    self.record_jump();
+                    self.open_file(path);
+                    return;
+                }
+
+                current_x += tab_len + 1; // +1 for space between tabs
+            }
+        }
+    }
+
+    /// タブを閉じる（未保存なら確認ダイアログを表示）
+    pub(crate) fn close_current_tab(&mut self) {
+        if self.is_unsaved() {
+            self.confirm_dialog = Some(ConfirmAction::CloseTab);
+        } else {
+            self.force_close_current_tab();
+        }
+    }
+
+    /// タブを強制的に閉じる（確認なし）
+    pub(crate) fn force_close_current_tab(&mut self) {
+        if let Some(current) = &self.pane.file_path.clone() {
+            if let Some(idx) = self.tabs.iter().position(|p| p == current) {
+                self.tabs.remove(idx);
+                self.unsaved_files.remove(current);
+                // 別のタブがあれば切り替え
+                if !self.tabs.is_empty() {
+                    let new_idx = idx.min(self.tabs.len() - 1);
+                    let new_path = self.tabs[new_idx].clone();
+                    self.open_file(&new_path);
+                } else {
+                    // タブがなくなったらクリア
+                    self.pane.file_path = None;
+                    self.pane.buffer = Rope::new();
+                    self.pane.saved_content.clear();
+                    self.pane.source_cache.clear();
+                    self.pane.highlight_cache = None;
+                    self.pane.line_offsets.clear();
+                    self.pane.max_line_width = 0;
+                    self.pane.buffer_dirty = true;
+                    self.pane.cursor_line = 0;
+                    self.pane.cursor_col = 0;
+                    self.pane.scroll_offset = 0;
+                    self.pane.horizontal_scroll = 0;
+                    self.pane.bookmarks.clear();
+                    self.needs_clear = true;
+                }
+            }
+        }
+    }
+
+    /// ペインを分割（分割中なら方向だけ変更）
+    pub(crate) fn split_pane(&mut self, direction: Direction) {
+        self.split_direction = direction;
+        if self.other_pane.is_none() {
+            self.other_pane = Some(EditorPane {
+                file_path: self.pane.file_path.clone(),
+                buffer: self.pane.buffer.clone(),
+                saved_content: self.pane.saved_content.clone(),
+                file_modified_time: self.pane.file_modified_time,
+                cursor_line: self.pane.cursor_line,
+                cursor_col: self.pane.cursor_col,
+                scroll_offset: self.pane.scroll_offset,
+                horizontal_scroll: self.pane.horizontal_scroll,
+                current_language: self.pane.current_language,
+                bookmarks: self.pane.bookmarks.clone(),
+                git_branch: self.pane.git_branch.clone(),
+                is_image_mode: self.pane.is_image_mode,
+                buffer_dirty: true,
+                ..EditorPane::default()
+            });
+        }
+        self.needs_clear = true;
+    }
+
+    /// もう一方のペインにフォーカスを移す
+    pub(crate) fn switch_pane(&mut self) {
+        self.sync_other_pane();
+        let Some(mut other) = self.other_pane.take() else {
+            return;
+        };
+        std::mem::swap(&mut self.pane, &mut other);
+        self.other_pane = Some(other);
+        self.focused_pane = 1 - self.focused_pane;
+        self.clear_selection();
+        self.pane.follow_cursor = true;
+        self.needs_clear = true;
+        // 画像はフォーカス中のペインにだけ表示するので読み込み直す
+        if self.pane.is_image_mode {
+            if let Some(path) = self.pane.file_path.clone() {
+                self.open_file(&path);
+            }
+        }
+    }
+
+    /// フォーカス中のペインを閉じる
+    pub(crate) fn close_pane(&mut self) {
+        if self.other_pane.is_some() {
+            self.switch_pane();
+            self.close_other_pane();
+        }
+    }
+
+    /// フォーカス中以外のペインを閉じる（未保存の内容はタブに保持）
+    pub(crate) fn close_other_pane(&mut self) {
+        self.sync_other_pane();
+        let Some(other) = self.other_pane.take() else {
+            return;
+        };
+        self.focused_pane = 0;
+        self.needs_clear = true;
+        if let Some(path) = other.file_path {
+            if !other.is_image_mode && other.buffer != other.saved_content && self.pane.file_path.as_ref() != Some(&path) {
+                self.unsaved_files.insert(path, UnsavedFile {
+                    buffer: other.buffer,
+                    saved_content: other.saved_content,
+                    cursor_line: other.cursor_line,
+                    cursor_col: other.cursor_col,
+                    scroll_offset: other.scroll_offset,
+                    horizontal_scroll: other.horizontal_scroll,
+                    modified_time: other.file_modified_time,
+                    externally_modified: false,
+                    bookmarks: other.bookmarks,
+                });
+            } else if !other.bookmarks.is_empty() && self.pane.file_path.as_ref() != Some(&path) {
+                self.file_bookmarks.insert(path, other.bookmarks);
+            }
+        }
+    }
+
+    /// 同じファイルを表示しているもう一方のペインに編集内容を反映
+    pub(crate) fn sync_other_pane(&mut self) {
+        let Some(other) = self.other_pane.as_mut() else {
+            return;
+        };
+        if other.is_image_mode || other.file_path != self.pane.file_path {
+            return;
+        }
+        if other.buffer != self.pane.buffer
+            || other.saved_content != self.pane.saved_content
+            || other.bookmarks != self.pane.bookmarks
+        {
+            other.buffer = self.pane.buffer.clone();
+            other.saved_content = self.pane.saved_content.clone();
+            other.file_modified_time = self.pane.file_modified_time;
+            other.bookmarks = self.pane.bookmarks.clone();
+            other.buffer_dirty = true;
+            other.cursor_line = other.cursor_line.min(other.buffer.len_lines().saturating_sub(1));
+            let line_len = other.buffer.line(other.cursor_line).chars().filter(|&c| c != '\n' && c != '\r').count();
+            other.cursor_col = other.cursor_col.min(line_len);
+        }
+    }
+
+    /// 指定ファイルが未保存か（フォーカス中以外のペイン・タブも含む）
+    pub(crate) fn is_path_unsaved(&self, path: &PathBuf) -> bool {
+        if self.pane.file_path.as_ref() == Some(path) {
+            return self.is_unsaved();
+        }
+        self.unsaved_files.contains_key(path)
+            || self.other_pane.as_ref().is_some_and(|p| {
+                p.file_path.as_ref() == Some(path) && p.buffer != p.saved_content
+            })
+    }
+
+    /// 検索を実行してマッチ位置を更新
+    pub(crate) fn search(&mut self) {
+        self.search_matches.clear();
+        self.search_index = 0;
+
+        if self.search_query.is_empty() {
+            return;
+        }
+
+        let query_chars: Vec<char> = self.search_query.chars().collect();
+        let query_len = query_chars.len();
+        let case_insensitive = self.search_case_insensitive;
+        let chars_eq = |a: char, b: char| {
+            a == b || (case_insensitive && a.to_lowercase().eq(b.to_lowercase()))
+        };
+
+        for (line_idx, line) in self.pane.buffer.lines().enumerate() {
+            let line_chars: Vec<char> = line.chars().collect();
+            if line_chars.len() < query_len {
+                continue;
+            }
+
+            // 文字単位で検索
+            for col in 0..=line_chars.len().saturating_sub(query_len) {
+                let mut matched = true;
+                for (i, &qc) in query_chars.iter().enumerate() {
+                    if !line_chars.get(col + i).is_some_and(|&c| chars_eq(c, qc)) {
+                        matched = false;
+                        break;
+                    }
+                }
+                if matched {
+                    self.search_matches.push((line_idx, col));
+                }
+            }
+        }
+
+        // 現在のカーソル位置以降の最初のマッチを選択
+        for (i, &(line, col)) in self.search_matches.iter().enumerate() {
+            if line > self.pane.cursor_line || (line == self.pane.cursor_line && col >= self.pane.cursor_col) {
+                self.search_index = i;
+                break;
+            }
+        }
+    }
+
+    /// 次のマッチに移動
+    pub(crate) fn next_match(&mut self) {
+        if self.search_matches.is_empty() {
+            return;
+        }
+        if self.search_index + 1 < self.search_matches.len() {
+            self.search_index += 1;
+        } else if self.search_wrap {
+            self.search_index = 0;
+        }
+        self.record_jump_before_match();
+        self.jump_to_match();
+    }
+
+    /// 前のマッチに移動
+    pub(crate) fn prev_match(&mut self) {
+        if self.search_matches.is_empty() {
+            return;
+        }
+        if self.search_index > 0 {
+            self.search_index -= 1;
+        } else if self.search_wrap {
+            self.search_index = self.search_matches.len() - 1;
+        }
+        self.record_jump_before_match();
+        self.jump_to_match();
+    }
+
+    /// マッチ間の移動前の位置をジャンプリストに記録（移動しない場合は記録しない）
+    fn record_jump_before_match(&mut self) {
+        let cursor = (self.pane.cursor_line, self.pane.cursor_col);
+        if self.search_matches.get(self.search_index).is_some_and(|&target| target != cursor) {
+            self.record_jump();
+        }
+    }
+
+    /// 現在のマッチ位置にジャンプ
+    pub(crate) fn jump_to_match(&mut self) {
+        if let Some(&(line, col)) = self.search_matches.get(self.search_index) {
+            self.pane.cursor_line = line;
+            self.pane.cursor_col = col;
+            self.pane.follow_cursor = true;
+        }
+    }
+
+    pub(crate) fn file_name(&self) -> String {
+        self.pane.file_path
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "New File".to_string())
+    }
+
+    /// ジャンプリストに記録する現在位置
+    pub(crate) fn current_jump_position(&self) -> Option<(PathBuf, usize, usize)> {
+        let path = self.pane.file_path.clone()?;
+        Some((path, self.pane.cursor_line, self.pane.cursor_col))
+    }
+
+    /// 大きく移動する前の位置をジャンプリストに記録（戻った先から記録すると先の履歴は破棄）
+    pub(crate) fn record_jump(&mut self) {
+        let Some(position) = self.current_jump_position() else {
+            return;
+        };
+        self.jump_list.truncate(self.jump_index);
+        if self.jump_list.last() != Some(&position) {
+            self.jump_list.push(position);
+            if self.jump_list.len() > JUMP_LIST_MAX {
+                self.jump_list.remove(0);
+            }
+        }
+        self.jump_index = self.jump_list.len();
+    }
+
+    /// ジャンプリストを戻る（Ctrl-O）
+    pub(crate) fn jump_back(&mut self) {
+        let Some(current) = self.current_jump_position() else {
+            return;
+        };
+        if self.jump_index == self.jump_list.len() {
+            // 戻った後に進めるよう現在位置も記録
+            if self.jump_list.last() != Some(&current) {
+                self.jump_list.push(current.clone());
+            }
+            self.jump_index = self.jump_list.len() - 1;
+        }
+        while self.jump_index > 0 {
+            self.jump_index -= 1;
+            let target = self.jump_list[self.jump_index].clone();
+            if target != current {
+                self.go_to_jump(target);
+                return;
+            }
+        }
+    }
+
+    /// ジャンプリストを進む（Alt-I）
+    pub(crate) fn jump_forward(&mut self) {
+        let current = self.current_jump_position();
+        while self.jump_index + 1 < self.jump_list.len() {
+            self.jump_index += 1;
+            let target = self.jump_list[self.jump_index].clone();
+            if Some(&target) != current.as_ref() {
+                self.go_to_jump(target);
+                return;
+            }
+        }
+    }
+
+    /// ジャンプリストの位置へ移動（別ファイルなら開く）
+    pub(crate) fn go_to_jump(&mut self, (path, line, col): (PathBuf, usize, usize)) {
+        if self.pane.file_path.as_ref() != Some(&path) {
+            if !path.is_file() {
+                return;
+            }
+            self.open_file(&path);
+        }
+        self.clear_selection();
+        self.pane.follow_cursor = true;
+        self.pane.cursor_line = line.min(self.pane.buffer.len_lines().saturating_sub(1));
+        self.pane.cursor_col = col.min(self.current_line_len());
+    }
+
+    /// 現在行のブックマークを切り替え
+    pub(crate) fn toggle_bookmark(&mut self) {
+        if self.pane.is_image_mode {
+            return;
+        }
+        let line = self.pane.cursor_line;
+        match self.pane.bookmarks.binary_search(&line) {
+            Ok(i) => {
+                self.pane.bookmarks.remove(i);
+            }
+            Err(i) => self.pane.bookmarks.insert(i, line),
+        }
+    }
+
+    /// 次（forwardがfalseなら前）のブックマークへ移動（端では反対側から巡回）
+    pub(crate) fn jump_to_bookmark(&mut self, forward: bool) {
+        let line = self.pane.cursor_line;
+        let bookmarks = &self.pane.bookmarks;
+        let target = if forward {
+            bookmarks.iter().find(|&&b| b > line).or(bookmarks.first())
+        } else {
+            bookmarks.iter().rev().find(|&&b| b < line).or(bookmarks.last())
+        };
+        if let Some(&target) = target {
+            self.go_to_line(target + 1);
+        }
+    }
+
+    /// カーソル位置の識別子の定義へ移動（同じファイル内のみ）
+    pub(crate) fn go_to_definition(&mut self) {
+        if self.pane.is_image_mode {
+            return;
+        }
+        let supported = self.pane.current_language.filter(|lang| self.syntax.definitions.contains_key(lang));
+        let Some(language) = supported else {
+            self.set_status_message("Go to definition is only available for Rust, Go, Python and JavaScript");
+            return;
+        };
+        self.update_cache();
+        let offset = self.pane.buffer.char_to_byte(self.cursor_to_char_idx());
+        let Some(def) = self.syntax.find_definition(&self.pane.source_cache, language, offset) else {
+            self.set_status_message("No definition found");
+            return;
+        };
+        let (line, col) = self.char_idx_to_pos(self.pane.buffer.byte_to_char(def));
+        self.record_jump();
+        self.clear_selection();
+        self.pane.follow_cursor = true;
+        self.pane.cursor_line = line;
+        self.pane.cursor_col = col;
+    }
+
+    /// 対応するファイル（ヘッダ・テストなど）へ切り替え
+    pub(crate) fn toggle_counterpart(&mut self) {
+        let Some(path) = self.pane.file_path.clone() else {
+            return;
+        };
+        let counterpart = counterpart_candidates(&path, &self.counterpart_rules).into_iter()
+            .find(|candidate| candidate.is_file());
+        match counterpart {
+            Some(counterpart) => {
+                self.record_jump();
+                self.open_file(&counterpart);
+            }
+            None => self.set_status_message("No counterpart found"),
+        }
+    }
+
+    /// カーソル行から始まるブロック（括弧または字下げ）を折りたたむ
+    pub(crate) fn fold_block(&mut self) {
+        if self.pane.is_image_mode {
+            return;
+        }
+        let line = self.pane.cursor_line;
+        match self.pane.fold_range_at(line) {
+            Some(end) => {
+                self.pane.add_fold(line, end);
+                self.pane.follow_cursor = true;
+            }
+            None => self.set_status_message("Nothing to fold"),
+        }
+    }
+
+    /// カーソル行の折りたたみを展開
+    pub(crate) fn unfold_block(&mut self) {
+        if !self.pane.remove_fold(self.pane.cursor_line) {
+            self.set_status_message("No fold at this line");
+        }
+    }
+
+    /// 指定行（1始まり）の行頭へ移動
+    pub(crate) fn go_to_line(&mut self, line: usize) {
+        self.record_jump();
+        self.clear_selection();
+        self.pane.follow_cursor = true;
+        self.pane.cursor_line = line.saturating_sub(1).min(self.pane.buffer.len_lines().saturating_sub(1));
+        self.pane.cursor_col = 0;
+    }
+
+    /// カーソル直前の単語を補完（候補が複数なら一覧を表示）
+    pub(crate) fn start_completion(&mut self) {
+        if self.pane.is_image_mode {
+            return;
+        }
+        let cursor = self.cursor_to_char_idx();
+        let line_start = self.pane.buffer.line_to_char(self.pane.cursor_line);
+        let before: Vec<char> = self.pane.buffer.slice(line_start..cursor).chars().collect();
+        let word_len = before.iter().rev().take_while(|&&c| is_word_char(c)).count();
+        if word_len == 0 {
+            return;
+        }
+        let prefix: String = before[before.len() - word_len..].iter().collect();
+
+        self.update_cache();
+        let mut candidates: Vec<String> = self.pane.source_cache
+            .split(|c: char| !is_word_char(c))
+            .filter(|word| word.len() > prefix.len() && word.starts_with(&prefix))
+            .map(str::to_string)
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+
+        let completion = Completion {
+            start: cursor - word_len,
+            prefix,
+            candidates,
+            selected: 0,
+        };
+        match completion.candidates.len() {
+            0 => {}
+            1 => self.insert_completion(&completion),
+            _ => self.completion = Some(completion),
+        }
+    }
+
+    /// 選択中の候補の残りの部分を挿入
+    pub(crate) fn insert_completion(&mut self, completion: &Completion) {
+        let Some(candidate) = completion.candidates.get(completion.selected) else {
+            return;
+        };
+        let rest = &candidate[completion.prefix.len()..];
+        self.add_to_tabs();
+        self.pane.follow_cursor = true;
+        let idx = completion.start + completion.prefix.chars().count();
+        self.pane.before_insert(idx, rest);
+        self.pane.buffer.insert(idx, rest);
+        self.pane.buffer_dirty = true;
+        self.char_idx_to_cursor(idx + rest.chars().count());
+    }
+
+    /// 補完候補の表示中のキー入力を処理（処理した場合はtrue）
+    pub(crate) fn handle_completion_key(&mut self, key: &KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let Some(completion) = self.completion.as_mut() else {
+            return false;
+        };
+        let last = completion.candidates.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Up => completion.selected = completion.selected.checked_sub(1).unwrap_or(last),
+            KeyCode::Char('p') if ctrl => completion.selected = completion.selected.checked_sub(1).unwrap_or(last),
+            KeyCode::Down => completion.selected = if completion.selected >= last { 0 } else { completion.selected + 1 },
+            KeyCode::Char('n') if ctrl => completion.selected = if completion.selected >= last { 0 } else { completion.selected + 1 },
+            KeyCode::Enter | KeyCode::Tab => {
+                if let Some(completion) = self.completion.take() {
+                    self.insert_completion(&completion);
+                }
+            }
+            KeyCode::Esc => self.completion = None,
+            KeyCode::Char('g') if ctrl => self.completion = None,
+            _ => {
+                // 他のキーは候補を閉じてから通常どおり処理
+                self.completion = None;
+                return false;
+            }
+        }
+        true
+    }
+
+    /// カーソル位置の単語のスペルの修正候補を表示
+    pub(crate) fn start_spell_suggestion(&mut self) {
+        if self.pane.is_image_mode {
+            return;
+        }
+        let Some(dictionary) = &self.spell_dictionary else {
+            self.set_status_message("Spell checking is off");
+            return;
+        };
+        let line_start = self.pane.buffer.line_to_char(self.pane.cursor_line);
+        let line = self.pane.buffer.line(self.pane.cursor_line).to_string();
+        let cursor = line.char_indices().nth(self.pane.cursor_col).map_or(line.len(), |(i, _)| i);
+        let Some((start, end)) = word_ranges(&line).into_iter().find(|&(start, end)| start <= cursor && cursor <= end) else {
+            self.set_status_message("No word at cursor");
+            return;
+        };
+        let word = &line[start..end];
+        if dictionary.contains(word) {
+            let message = format!("\"{}\" is spelled correctly", word);
+            self.set_status_message(message);
+            return;
+        }
+        let candidates = dictionary.suggestions(word);
+        if candidates.is_empty() {
+            let message = format!("No suggestions for \"{}\"", word);
+            self.set_status_message(message);
+            return;
+        }
+        self.spell_suggestion = Some(SpellSuggestion {
+            start: line_start + line[..start].chars().count(),
+            end: line_start + line[..end].chars().count(),
+            candidates,
+            selected: 0,
+        });
+    }
+
+    /// 選択中の候補で単語を置き換える
+    pub(crate) fn replace_spelling(&mut self, suggestion: &SpellSuggestion) {
+        let Some(candidate) = suggestion.candidates.get(suggestion.selected) else {
+            return;
+        };
+        self.add_to_tabs();
+        self.pane.follow_cursor = true;
+        self.pane.before_remove(suggestion.start, suggestion.end);
+        self.pane.buffer.remove(suggestion.start..suggestion.end);
+        self.pane.before_insert(suggestion.start, candidate);
+        self.pane.buffer.insert(suggestion.start, candidate);
+        self.pane.buffer_dirty = true;
+        self.char_idx_to_cursor(suggestion.start + candidate.chars().count());
+    }
+
+    /// スペルの修正候補の表示中のキー入力を処理（処理した場合はtrue）
+    pub(crate) fn handle_spell_suggestion_key(&mut self, key: &KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let Some(suggestion) = self.spell_suggestion.as_mut() else {
+            return false;
+        };
+        let last = suggestion.candidates.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Up => suggestion.selected = suggestion.selected.checked_sub(1).unwrap_or(last),
+            KeyCode::Char('p') if ctrl => suggestion.selected = suggestion.selected.checked_sub(1).unwrap_or(last),
+            KeyCode::Down => suggestion.selected = if suggestion.selected >= last { 0 } else { suggestion.selected + 1 },
+            KeyCode::Char('n') if ctrl => suggestion.selected = if suggestion.selected >= last { 0 } else { suggestion.selected + 1 },
+            KeyCode::Enter | KeyCode::Tab => {
+                if let Some(suggestion) = self.spell_suggestion.take() {
+                    self.replace_spelling(&suggestion);
+                }
+            }
+            KeyCode::Esc => self.spell_suggestion = None,
+            KeyCode::Char('g') if ctrl => self.spell_suggestion = None,
+            _ => {
+                // 他のキーは候補を閉じてから通常どおり処理
+                self.spell_suggestion = None;
+                return false;
+            }
+        }
+        true
+    }
+
+    /// JSON/TOML/YAMLのバッファを整形（パースできなければステータスバーに表示）
+    pub(crate) fn format_buffer(&mut self) {
+        if self.pane.is_image_mode {
+            return;
+        }
+        let Some(language) = self.pane.current_language else {
+            self.set_status_message("Format is only available for JSON, TOML and YAML");
+            return;
+        };
+        self.update_cache();
+        let formatted = match format_document(language, &self.pane.source_cache) {
+            Ok(formatted) => formatted,
+            Err(message) => {
+                self.set_status_message(message);
+                return;
+            }
+        };
+        self.replace_buffer(&formatted);
+    }
+
+    /// 設定された外部フォーマッタで保存前に整形（失敗したら整形せずステータスバーに表示）
+    pub(crate) fn run_format_on_save(&mut self) {
+        let (Some(path), Some(language)) = (self.pane.file_path.clone(), self.pane.current_language) else {
+            return;
+        };
+        let Some(command) = self.format_on_save.get(&language).cloned() else {
+            return;
+        };
+        self.update_cache();
+        match run_formatter(&command, &path, &self.pane.source_cache) {
+            Ok(formatted) => self.replace_buffer(&formatted),
+            Err(message) => self.set_status_message(message),
+        }
+    }
+
+    /// 数引数の入力キーかどうか（C-u、M-数字、数引数入力中の数字）
+    pub(crate) fn is_prefix_arg_key(&self, key: &KeyEvent) -> bool {
+        if self.confirm_dialog.is_some()
+            || self.search_mode
+            || self.vim_command.is_some()
+            || self.command_palette.is_some()
+            || self.recent_list.is_some()
+            || self.file_finder.is_some()
+            || self.prompt.is_some()
+            || !self.pending_keys.is_empty()
+        {
+            return false;
+        }
+        if self.keymap.action_for(key) == Some(Action::UniversalArgument) {
+            return true;
+        }
+        match key.code {
+            KeyCode::Char(c) if c.is_ascii_digit() => {
+                key.modifiers == KeyModifiers::ALT
+                    || (key.modifiers.is_empty() && self.prefix_arg.is_some())
+                    // Vimのノーマル・ビジュアルモードでは数字で回数を指定（0は除く）
+                    || (key.modifiers.is_empty()
+                        && c != '0'
+                        && matches!(self.vim_mode, Some(VimMode::Normal | VimMode::Visual)))
+            }
+            _ => false,
+        }
+    }
+
+    /// 数引数の入力を処理
+    pub(crate) fn handle_prefix_arg_key(&mut self, key: &KeyEvent) {
+        let universal = self.keymap.action_for(key) == Some(Action::UniversalArgument);
+        match (key.code, self.prefix_arg) {
+            (_, Some(arg)) if universal && !arg.has_digits => {
+                // C-u C-u ... で4倍ずつ増やす
+                self.prefix_arg = Some(PrefixArg { count: arg.count.saturating_mul(4), has_digits: false });
+            }
+            (_, Some(_)) if universal => {}
+            (_, None) if universal => {
+                self.prefix_arg = Some(PrefixArg { count: 4, has_digits: false });
+            }
+            (KeyCode::Char(c), arg) => {
+                let digit = c.to_digit(10).unwrap_or(0) as usize;
+                let count = match arg {
+                    Some(arg) if arg.has_digits => arg.count.saturating_mul(10).saturating_add(digit),
+                    _ => digit,
+                };
+                self.prefix_arg = Some(PrefixArg { count, has_digits: true });
+            }
+            _ => {}
+        }
+    }
+
+    /// キー入力を処理（終了する場合はtrue）
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        // 数引数の入力キー以外が来たら保留中の数引数を消費する
+        let prefix = if self.is_prefix_arg_key(&key) {
+            None
+        } else {
+            self.prefix_arg.take()
+        };
+        self.last_command = std::mem::replace(&mut self.this_command, CommandKind::Other);
+
+        // 確認ダイアログ中の場合
+        if let Some(action) = self.confirm_dialog {
+            return match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    self.confirm_dialog = None;
+                    match action {
+                        ConfirmAction::Quit => true,
+                        ConfirmAction::CloseTab => {
+                            self.force_close_current_tab();
+                            false
+                        }
+                        ConfirmAction::Overwrite => {
+                            if let Some(path) = self.pending_save_as.take() {
+                                self.write_as(path);
+                            }
+                            false
+                        }
+                    }
+                }
+                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                    self.confirm_dialog = None;
+                    self.pending_save_as = None;
+                    false
+                }
+                _ => false,
+            };
+        }
+
+        // 検索モード中の場合
+        if self.search_mode {
+            self.handle_search_key(&key);
+            return false;
+        }
+
+        // Vimのコマンドライン入力中
+        if self.vim_command.is_some() {
+            return self.handle_vim_command_key(&key);
+        }
+
+        // コマンドパレット表示中
+        if self.command_palette.is_some() {
+            return self.handle_palette_key(&key);
+        }
+
+        // 最近のファイル一覧表示中
+        if self.recent_list.is_some() {
+            self.handle_recent_list_key(&key);
+            return false;
+        }
+
+        // ファイル検索の一覧表示中
+        if self.file_finder.is_some() {
+            self.handle_file_finder_key(&key);
+            return false;
+        }
+
+        // 入力補完の候補を表示中（候補の操作以外のキーは通常どおり処理）
+        if self.completion.is_some() && self.handle_completion_key(&key) {
+            return false;
+        }
+        if self.spell_suggestion.is_some() && self.handle_spell_suggestion_key(&key) {
+            return false;
+        }
+
+        // 差分表示中
+        if self.diff_view.is_some() {
+            self.handle_diff_key(&key);
+            return false;
+        }
+
+        // 画面下部の入力欄に入力中
+        if self.prompt.is_some() {
+            self.handle_prompt_key(&key);
+            return false;
+        }
+
+        // ターミナルにフォーカス中（C-x で始まるキー以外はシェルに送り、C-x C-x はシェルに C-x を送る）
+        let is_ctrl_x = key.code == KeyCode::Char('x') && key.modifiers == KeyModifiers::CONTROL;
+        if self.terminal_focused && is_ctrl_x && self.pending_keys == [KeySpec::from_event(&key)] {
+            self.pending_keys.clear();
+            if let Some(terminal) = self.terminal.as_mut() {
+                terminal.send_key(&key);
+            }
+            return false;
+        }
+
+        // 複数キーの入力途中（C-x の後など）
+        if !self.pending_keys.is_empty() {
+            return self.dispatch_key(&key, prefix);
+        }
+
+        if self.terminal_focused && !is_ctrl_x {
+            if let Some(terminal) = self.terminal.as_mut() {
+                terminal.send_key(&key);
+            }
+            return false;
+        }
+
+        // サイドバーにフォーカス中（絞り込み入力・項目選択）
+        if self.sidebar_focused && self.handle_sidebar_key(&key) {
+            return false;
+        }
+
+        // 画像モードの拡大・移動
+        if self.pane.is_image_mode && self.handle_image_key(&key) {
+            return false;
+        }
+
+        // 数引数の入力（C-u 10 C-n など）
+        if self.is_prefix_arg_key(&key) {
+            self.handle_prefix_arg_key(&key);
+            return false;
+        }
+
+        if let Some(quit) = self.handle_vim_key(&key, prefix) {
+            return quit;
+        }
+
+        self.dispatch_key(&key, prefix)
+    }
+
+    /// キーバインドに従ってアクションを実行（終了する場合はtrue）
+    pub(crate) fn dispatch_key(&mut self, key: &KeyEvent, prefix: Option<PrefixArg>) -> bool {
+        self.pending_keys.push(KeySpec::from_event(key));
+        match self.keymap.lookup(&self.pending_keys) {
+            KeyLookup::Action(action) => {
+                self.pending_keys.clear();
+                self.run_action(action, prefix)
+            }
+            KeyLookup::Prefix => {
+                // 続きのキーまで数引数を持ち越す
+                self.prefix_arg = prefix;
+                false
+            }
+            KeyLookup::Unbound => {
+                let in_sequence = self.pending_keys.len() > 1;
+                self.pending_keys.clear();
+                // 割り当てのない文字キーはそのまま入力
+                if let KeyCode::Char(c) = key.code {
+                    if !in_sequence && (key.modifiers - KeyModifiers::SHIFT).is_empty() {
+                        let count = prefix.map_or(1, |p| p.count);
+                        self.clear_selection();
+                        (0..count).for_each(|_| self.insert_typed_char(c));
+                    }
+                }
+                false
+            }
+        }
+    }
+
+    /// 検索モードでのキー入力を処理
+    pub(crate) fn handle_search_key(&mut self, key: &KeyEvent) {
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            match key.code {
+                KeyCode::Char('h') => {
+                    // Ctrl+H: Backspace
+                    self.search_query.pop();
+                    self.search();
+                    self.jump_to_match();
+                }
+                KeyCode::Char('u') => {
+                    // Ctrl+U: クリア
+                    self.search_query.clear();
+                    self.search();
+                }
+                // Ctrl+N/Ctrl+G: 次のマッチ
+                KeyCode::Char('n') | KeyCode::Char('g') => self.next_match(),
+                // Ctrl+P: 前のマッチ
+                KeyCode::Char('p') => self.prev_match(),
+                KeyCode::Char('c') => {
+                    // Ctrl+C: 検索終了
+                    self.search_mode = false;
+                    self.search_matches.clear();
+                }
+                _ => {}
+            }
+        } else if key.modifiers.contains(KeyModifiers::ALT) {
+            match key.code {
+                KeyCode::Char('c') => {
+                    // Alt+C: 大文字・小文字の区別を切り替え
+                    self.search_case_insensitive = !self.search_case_insensitive;
+                    self.search();
+                    self.jump_to_match();
+                }
+                // Alt+W: 最後のマッチから最初に戻るかを切り替え
+                KeyCode::Char('w') => self.search_wrap = !self.search_wrap,
+                _ => {}
+            }
+        } else {
+            match key.code {
+                KeyCode::Esc => {
+                    self.search_mode = false;
+                    self.search_matches.clear();
+                }
+                KeyCode::Enter => {
+                    if key.modifiers.contains(KeyModifiers::SHIFT) {
+                        self.prev_match();
+                    } else {
+                        self.next_match();
+                    }
+                }
+                KeyCode::Backspace => {
+                    self.search_query.pop();
+                    self.search();
+                    self.jump_to_match();
+                }
+                KeyCode::Char(c) => {
+                    self.search_query.push(c);
+                    self.search();
+                    self.jump_to_match();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// アクションを実行（終了する場合はtrue）
+    pub(crate) fn run_action(&mut self, action: Action, prefix: Option<PrefixArg>) -> bool {
+        let count = prefix.map_or(1, |p| p.count);
+        match action {
+            Action::MoveUp => { self.clear_selection_unless_mark(); self.move_up(count); }
+            Action::MoveDown => { self.clear_selection_unless_mark(); self.move_down(count); }
+            Action::MoveLeft => { self.clear_selection_unless_mark(); self.move_left(count); }
+            Action::MoveRight => { self.clear_selection_unless_mark(); self.move_right(count); }
+            Action::LineStart => { self.clear_selection_unless_mark(); self.move_to_line_start(); }
+            Action::LineEnd => { self.clear_selection_unless_mark(); self.move_to_line_end(); }
+            Action::DeleteBackward => { self.clear_selection(); (0..count).for_each(|_| self.delete_char_backspace()); }
+            Action::DeleteForward => { self.clear_selection(); (0..count).for_each(|_| self.delete_char_delete()); }
+            Action::Newline => { self.clear_selection(); (0..count).for_each(|_| self.insert_newline()); }
+            Action::KillLine => self.kill_line(prefix.map(|p| p.count)),
+            Action::TransposeChars => (0..count).for_each(|_| self.transpose_chars()),
+            Action::SetMark => self.set_mark(),
+            // 選択解除
+            Action::Cancel => {
+                self.clear_selection();
+                self.pane.cursors.clear();
+            }
+            Action::KillRegion => {
+                // 選択範囲がある場合はkill、ない場合はタブを閉じる
+                if self.selection.is_some() {
+                    self.kill_region();
+                } else {
+                    self.close_current_tab();
+                }
+            }
+            Action::CopyRegion => self.copy_region(),
+            Action::Yank => self.yank(),
+            Action::YankPop => self.yank_pop(),
+            Action::UniversalArgument => {}
+            Action::Save if self.pane.file_path.is_none() => self.start_save_as(),
+            Action::Save => { self.save_current_file(); }
+            Action::SaveAs => self.start_save_as(),
+            Action::SaveAll => {
+                if let Err(e) = self.save_all() {
+                    self.set_status_message(format!("Failed to save all files: {}", e));
+                }
+            }
+            Action::Copy => {
+                if let Some(text) = self.get_selected_text() {
+                    self.copy_to_clipboard_osc52(&text);
+                }
+            }
+            Action::Quit => {
+                // 選択範囲がある場合はコピー、ない場合は終了
+                if self.selection.is_some() {
+                    if let Some(text) = self.get_selected_text() {
+                        self.copy_to_clipboard_osc52(&text);
+                    }
+                } else if self.has_unsaved_tabs() {
+                    self.confirm_dialog = Some(ConfirmAction::Quit);
+                } else {
+                    return true;
+                }
+            }
+            Action::CloseTab => self.close_current_tab(),
+            Action::Search => {
+                // 検索モード開始（検索前の位置に戻れるよう記録）
+                self.record_jump();
+                self.search_mode = true;
+                self.search_query.clear();
+                self.search_matches.clear();
+            }
+            Action::GoToLine => {
+                self.prompt = Some(Prompt { kind: PromptKind::GoToLine, input: String::new() });
+            }
+            Action::CommandPalette => self.command_palette = Some(FilterList::default()),
+            Action::RecentFiles => self.recent_list = Some(FilterList::default()),
+            Action::FindFile => {
+                let roots = self.roots.iter().map(|root| root.path.clone()).collect();
+                self.file_finder = Some(FileFinder::start(roots));
+            }
+            Action::Complete => self.start_completion(),
+            Action::FormatDocument => self.format_buffer(),
+            Action::NextTab => self.next_tab(),
+            Action::PrevTab => self.prev_tab(),
+            Action::ToggleWrap => self.toggle_wrap_lines(),
+            Action::ToggleAnsiColors => {
+                self.pane.ansi_colors = !self.pane.ansi_colors;
+                self.pane.horizontal_scroll = 0;
+                self.pane.follow_cursor = true;
+                self.set_status_message(if self.pane.ansi_colors { "ANSI colors enabled" } else { "ANSI colors disabled" });
+            }
+            Action::ToggleSmartInsert => {
+                self.smart_insert = !self.smart_insert;
+                self.set_status_message(if self.smart_insert { "Smart insert enabled" } else { "Smart insert disabled" });
+            }
+            Action::ToggleSidebar => {
+                self.sidebar_visible = !self.sidebar_visible;
+                self.sidebar_focused = false;
+                self.needs_clear = true;
+            }
+            Action::FocusSidebar => {
+                if !self.sidebar_visible {
+                    self.sidebar_visible = true;
+                    self.needs_clear = true;
+                }
+                self.sidebar_focused = true;
+                self.sidebar_selected = self.sidebar_scroll;
+            }
+            Action::AddFolder => self.prompt = Some(Prompt { kind: PromptKind::AddFolder, input: String::new() }),
+            Action::RemoveFolder => self.remove_workspace_folder(),
+            Action::ToggleTerminal => self.toggle_terminal(),
+            Action::SplitBelow => self.split_pane(Direction::Vertical),
+            Action::SplitRight => self.split_pane(Direction::Horizontal),
+            Action::OtherPane => self.switch_pane(),
+            Action::ClosePane => self.close_pane(),
+            Action::CloseOtherPanes => self.close_other_pane(),
+            Action::ShowDiff => self.show_diff(),
+            Action::ToggleBookmark => self.toggle_bookmark(),
+            Action::NextBookmark => self.jump_to_bookmark(true),
+            Action::PrevBookmark => self.jump_to_bookmark(false),
+            Action::JumpBack => self.jump_back(),
+            Action::JumpForward => self.jump_forward(),
+            Action::GoToDefinition => self.go_to_definition(),
+            Action::ToggleCounterpart => self.toggle_counterpart(),
+            Action::FoldBlock => self.fold_block(),
+            Action::UnfoldBlock => self.unfold_block(),
+            Action::AddCursorAtNextMatch => self.add_cursor_at_next_match(),
+            Action::SpellSuggest => self.start_spell_suggestion(),
+            Action::ScrollUp => self.pane.scroll_offset = self.pane.lines_above(self.pane.scroll_offset, 5),
+            Action::ScrollDown => self.pane.scroll_offset = self.pane.lines_below(self.pane.scroll_offset, 5),
+            Action::ScrollLeft => self.pane.horizontal_scroll = self.pane.horizontal_scroll.saturating_sub(5),
+            Action::ScrollRight => {
+                let visible_width = self.pane.editor_area.width.saturating_sub(2) as usize;
+                let ln_width = self.line_number_width();
+                let content_width = visible_width.saturating_sub(ln_width);
+                let max_scroll = self.pane.max_line_width.saturating_sub(content_width);
+                self.pane.horizontal_scroll = (self.pane.horizontal_scroll + 5).min(max_scroll);
+            }
+        }
+        false
+    }
+
+    /// コマンドパレットに表示するアクション（入力した語をすべて含むもの）
+    pub(crate) fn palette_actions(&self) -> Vec<Action> {
+        let Some(palette) = self.command_palette.as_ref() else {
+            return Vec::new();
+        };
+        Action::ALL.iter()
+            .copied()
+            .filter(|a| !matches!(a, Action::CommandPalette | Action::UniversalArgument))
+            // 空白なしの入力（"goto" など）でも一致させる
+            .filter(|a| palette.matches(&format!("{} {}", a.title().replace(' ', ""), a.name())))
+            .collect()
+    }
+
+    /// コマンドパレットのキー入力を処理（終了する場合はtrue）
+    pub(crate) fn handle_palette_key(&mut self, key: &KeyEvent) -> bool {
+        let actions = self.palette_actions();
+        let Some(palette) = self.command_palette.as_mut() else {
+            return false;
+        };
+        match palette.handle_key(key, actions.len()) {
+            FilterListInput::Continue => {}
+            FilterListInput::Cancel => self.command_palette = None,
+            FilterListInput::Submit => {
+                let selected = actions.get(palette.selected).copied();
+                self.command_palette = None;
+                if let Some(action) = selected {
+                    return self.run_action(action, None);
+                }
+            }
+        }
+        false
+    }
+
+    /// 最近開いたファイルを記録（重複は先頭へ移動）
+    pub(crate) fn add_recent_file(&mut self, path: &Path) {
+        self.recent_files.retain(|p| p != path);
+        self.recent_files.insert(0, path.to_path_buf());
+        self.recent_files.truncate(RECENT_FILES_MAX);
+        save_recent_files(&self.recent_files);
+    }
+
+    /// 最近のファイル一覧に表示するファイル（現在のファイルと存在しないものは除く）
+    pub(crate) fn recent_list_files(&self) -> Vec<PathBuf> {
+        let Some(list) = self.recent_list.as_ref() else {
+            return Vec::new();
+        };
+        self.recent_files.iter()
+            .filter(|p| self.pane.file_path.as_ref() != Some(*p) && p.is_file())
+            .filter(|p| list.matches(&self.display_path(p)))
+            .cloned()
+            .collect()
+    }
+
+    /// ルートからの相対パス（複数ルートならルート名付き、ルート外なら絶対パス）
+    pub(crate) fn display_path(&self, path: &Path) -> String {
+        for root in &self.roots {
+            if let Ok(rel) = path.strip_prefix(&root.path) {
+                if self.roots.len() > 1 {
+                    return Path::new(&root.name()).join(rel).display().to_string();
+                }
+                return rel.display().to_string();
+            }
+        }
+        path.display().to_string()
+    }
+
+    /// 最近のファイル一覧のキー入力を処理
+    pub(crate) fn handle_recent_list_key(&mut self, key: &KeyEvent) {
+        let files = self.recent_list_files();
+        let Some(list) = self.recent_list.as_mut() else {
+            return;
+        };
+        match list.handle_key(key, files.len()) {
+            FilterListInput::Continue => {}
+            FilterListInput::Cancel => self.recent_list = None,
+            FilterListInput::Submit => {
+                let selected = files.get(list.selected).cloned();
+                self.recent_list = None;
+                if let Some(path) = selected {
+                    self.record_jump();
+                    self.open_file(&path);
+                }
+            }
+        }
+    }
+
+    /// ファイル検索の一覧に列挙済みのファイルを加える
+    pub(crate) fn receive_found_files(&mut self) {
+        let Some(mut finder) = self.file_finder.take() else {
+            return;
+        };
+        finder.receive(|path| self.display_path(path));
+        self.file_finder = Some(finder);
+    }
+
+    /// ファイル検索の一覧のキー入力を処理
+    pub(crate) fn handle_file_finder_key(&mut self, key: &KeyEvent) {
+        let Some(finder) = self.file_finder.as_mut() else {
+            return;
+        };
+        match finder.list.handle_key(key, finder.matches.len()) {
+            FilterListInput::Continue => finder.update_matches(),
+            FilterListInput::Cancel => self.file_finder = None,
+            FilterListInput::Submit => {
+                let selected = finder.selected_file().cloned();
+                self.file_finder = None;
+                if let Some(path) = selected {
+                    self.record_jump();
+                    self.open_file(&path);
+                }
+            }
+        }
+    }
+
+    /// 保存済みの内容との差分を表示
+    pub(crate) fn show_diff(&mut self) {
+        if self.pane.is_image_mode {
+            return;
+        }
+        self.update_cache();
+        self.diff_view = Some(DiffView::new(&self.pane.saved_content, &self.pane.source_cache));
+        self.needs_clear = true;
+    }
+
+    /// 差分表示中のキー入力を処理（Escで閉じる）
+    pub(crate) fn handle_diff_key(&mut self, key: &KeyEvent) {
+        let visible_height = self.pane.editor_area.height.saturating_sub(2) as usize;
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let Some(diff) = self.diff_view.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.diff_view = None;
+                self.needs_clear = true;
+            }
+            KeyCode::Char('g') if ctrl => {
+                self.diff_view = None;
+                self.needs_clear = true;
+            }
+            KeyCode::Up => diff.scroll_by(-1, visible_height),
+            KeyCode::Char('p') if ctrl => diff.scroll_by(-1, visible_height),
+            KeyCode::Down => diff.scroll_by(1, visible_height),
+            KeyCode::Char('n') if ctrl => diff.scroll_by(1, visible_height),
+            KeyCode::PageUp => diff.scroll_by(-(visible_height as isize), visible_height),
+            KeyCode::PageDown | KeyCode::Char(' ') => diff.scroll_by(visible_height as isize, visible_height),
+            _ => {}
+        }
+    }
+
+    /// 画面下部の入力欄のキー入力を処理
+    pub(crate) fn handle_prompt_key(&mut self, key: &KeyEvent) {
+        let Some(prompt) = self.prompt.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => self.prompt = None,
+            KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::CONTROL) => self.prompt = None,
+            KeyCode::Backspace => {
+                prompt.input.pop();
+            }
+            KeyCode::Char(c) => prompt.input.push(c),
+            KeyCode::Enter => {
+                if let Some(Prompt { kind, input }) = self.prompt.take() {
+                    match kind {
+                        PromptKind::GoToLine => {
+                            if let Ok(line) = input.trim().parse::<usize>() {
+                                self.go_to_line(line);
+                            }
+                        }
+                        PromptKind::SaveAs => self.save_as(input.trim()),
+                        PromptKind::AddFolder => self.add_workspace_folder(input.trim()),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Vimのモード表示
+    pub(crate) fn vim_mode_label(&self) -> &'static str {
+        match self.vim_mode {
+            Some(VimMode::Normal) => " [NORMAL]",
+            Some(VimMode::Insert) => " [INSERT]",
+            Some(VimMode::Visual) => " [VISUAL]",
+            None => "",
+        }
+    }
+
+    /// Vimのキー入力を処理（Vimのキーとして処理しなかった場合はNone）
+    pub(crate) fn handle_vim_key(&mut self, key: &KeyEvent, prefix: Option<PrefixArg>) -> Option<bool> {
+        // マウス操作やCtrl-Gで選択が解除されていたらノーマルモードに戻す
+        if self.vim_mode == Some(VimMode::Visual) && !self.mark_active {
+            self.vim_mode = Some(VimMode::Normal);
+        }
+        let mode = self.vim_mode?;
+        if mode == VimMode::Insert {
+            if key.code != KeyCode::Esc {
+                return None;
+            }
+            // Vimと同様にカーソルを1文字戻す
+            self.vim_mode = Some(VimMode::Normal);
+            self.pane.cursor_col = self.pane.cursor_col.saturating_sub(1);
+            return Some(false);
+        }
+        // Ctrlなどの修飾キー付きは通常のキーバインドで処理
+        if !(key.modifiers - KeyModifiers::SHIFT).is_empty() {
+            return None;
+        }
+
+        let count = prefix.map_or(1, |p| p.count);
+        let pending = self.vim_pending.take();
+        match key.code {
+            KeyCode::Char('h') | KeyCode::Backspace => {
+                self.clear_selection_unless_mark();
+                self.move_left(count.min(self.pane.cursor_col));
+            }
+            KeyCode::Char('l') => {
+                self.clear_selection_unless_mark();
+                self.move_right(count.min(self.current_line_len().saturating_sub(self.pane.cursor_col)));
+            }
+            KeyCode::Char('j') | KeyCode::Enter => {
+                self.clear_selection_unless_mark();
+                self.move_down(count);
+            }
+            KeyCode::Char('k') => {
+                self.clear_selection_unless_mark();
+                self.move_up(count);
+            }
+            KeyCode::Char('d') | KeyCode::Char('x') if mode == VimMode::Visual => self.vim_visual_operator(true),
+            KeyCode::Char('y') if mode == VimMode::Visual => self.vim_visual_operator(false),
+            KeyCode::Char('x') => {
+                // 行末を越えて次の行とは結合しない
+                self.clear_selection();
+                let count = count.min(self.current_line_len().saturating_sub(self.pane.cursor_col));
+                (0..count).for_each(|_| self.delete_char_delete());
+            }
+            KeyCode::Char('d') => match pending {
+                Some(('d', count)) => {
+                    self.clear_selection();
+                    self.move_to_line_start();
+                    self.kill_line(Some(count));
+                }
+                _ => self.vim_pending = Some(('d', count)),
+            },
+            KeyCode::Char('i') if mode == VimMode::Normal => {
+                self.clear_selection();
+                self.vim_mode = Some(VimMode::Insert);
+            }
+            KeyCode::Char('a') if mode == VimMode::Normal => {
+                self.clear_selection();
+                self.move_right(1.min(self.current_line_len().saturating_sub(self.pane.cursor_col)));
+                self.vim_mode = Some(VimMode::Insert);
+            }
+            KeyCode::Char('v') if mode == VimMode::Visual => {
+                self.clear_selection();
+                self.vim_mode = Some(VimMode::Normal);
+            }
+            KeyCode::Char('v') => {
+                self.set_mark();
+                self.vim_mode = Some(VimMode::Visual);
+            }
+            KeyCode::Char('c') if matches!(pending, Some(('z', _))) => self.fold_block(),
+            KeyCode::Char('o') if matches!(pending, Some(('z', _))) => self.unfold_block(),
+            KeyCode::Char('=') if matches!(pending, Some(('z', _))) => self.start_spell_suggestion(),
+            KeyCode::Char('z') => self.vim_pending = Some(('z', count)),
+            KeyCode::Char(':') => self.vim_command = Some(String::new()),
+            // 多くの端末ではCtrl-IがTabとして届くため、Vimと同様にTabで進む
+            KeyCode::Tab => return Some(self.run_action(Action::JumpForward, prefix)),
+            KeyCode::Esc => {
+                self.clear_selection();
+                self.pane.cursors.clear();
+                self.vim_mode = Some(VimMode::Normal);
+            }
+            // 未割り当ての文字は入力しない
+            KeyCode::Char(_) => {}
+            _ => return None,
+        }
+        Some(false)
+    }
+
+    /// ビジュアルモードの選択範囲（カーソル位置の文字を含む）を削除またはコピー
+    pub(crate) fn vim_visual_operator(&mut self, delete: bool) {
+        if let Some(sel) = self.selection {
+            let (start, (end_line, end_col)) = sel.normalized();
+            let end_idx = (self.pos_to_char_idx(end_line, end_col) + 1).min(self.pane.buffer.len_chars());
+            let end_line = self.pane.buffer.char_to_line(end_idx);
+            let end_col = end_idx - self.pane.buffer.line_to_char(end_line);
+            self.selection = Some(Selection { start, end: (end_line, end_col) });
+            if delete {
+                self.kill_region();
+            } else {
+                self.copy_region();
+                self.pane.cursor_line = start.0;
+                self.pane.cursor_col = start.1;
+            }
+        }
+        self.clear_selection();
+        self.vim_mode = Some(VimMode::Normal);
+    }
+
+    /// 現在のモードに合わせたカーソルの形
+    pub fn cursor_shape(&self) -> CursorShape {
+        // ターミナルではシェル側の設定に任せる
+        if self.terminal_visible && self.terminal_focused {
+            return CursorShape::Default;
+        }
+        let text_input = self.search_mode
+            || self.vim_command.is_some()
+            || self.prompt.is_some()
+            || self.sidebar_focused;
+        match self.vim_mode {
+            Some(VimMode::Normal | VimMode::Visual) if !text_input => CursorShape::Block,
+            _ => CursorShape::Bar,
+        }
+    }
+
+    /// Vimのコマンドライン（:w など）のキー入力を処理（終了する場合はtrue）
+    pub(crate) fn handle_vim_command_key(&mut self, key: &KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Esc => self.vim_command = None,
+            KeyCode::Enter => return self.run_vim_command(),
+            KeyCode::Backspace => {
+                if let Some(command) = self.vim_command.as_mut() {
+                    if command.pop().is_none() {
+                        self.vim_command = None;
+                    }
+                }
+            }
+            KeyCode::Char(c) => {
+                if let Some(command) = self.vim_command.as_mut() {
+                    command.push(c);
+                }
+            }
+            _ => {}
+        }
+        false
+    }
+
+    /// Vimのコマンドを実行（終了する場合はtrue）
+    pub(crate) fn run_vim_command(&mut self) -> bool {
+        let Some(command) = self.vim_command.take() else {
+            return false;
+        };
+        let command = command.trim();
+        // ":w ファイル名" は別名で保存
+        if let Some(path) = command.strip_prefix("w ") {
+            self.save_as(path.trim());
+            return false;
+        }
+        if matches!(command, "w" | "wq" | "x") && self.pane.file_path.is_none() {
+            self.start_save_as();
+            return false;
+        }
+        // 保存に失敗した場合は終了しない
+        if matches!(command, "w" | "wq" | "x") && !self.save_current_file() {
+            return false;
+        }
+        match command {
+            "q" | "wq" | "x" => {
+                if self.has_unsaved_tabs() {
+                    self.confirm_dialog = Some(ConfirmAction::Quit);
+                    false
+                } else {
+                    true
+                }
+            }
+            "q!" => true,
+            _ => false,
+        }
+    }
+
+    pub(crate) fn update_scroll(&mut self) {
+        // 検索・行ジャンプなどで隠れた行にカーソルが移動したら展開し、
+        // 隠れた行からは表示を始めない
+        self.pane.reveal_line(self.pane.cursor_line);
+        self.pane.scroll_offset = self.pane.visible_line(self.pane.scroll_offset);
+        if !self.pane.follow_cursor {
+            return;
+        }
+
+        // 縦スクロール（カーソルの上下にscroll_margin行を残す。画面の高さの半分未満に抑え、
+        // バッファの末尾より下にはスクロールしない。行数は折りたたみで隠れた行を除いて数える）
+        let visible_height = self.pane.editor_area.height.saturating_sub(2) as usize;
+        let margin = self.scroll_margin.min(visible_height.saturating_sub(1) / 2);
+        let lines_below = self.pane.visible_rows_between(self.pane.cursor_line + 1, self.pane.buffer.len_lines());
+        let bottom_margin = margin.min(lines_below);
+        if visible_height > 0 {
+            let rows_above = (self.pane.cursor_line >= self.pane.scroll_offset)
+                .then(|| self.pane.visible_rows_between(self.pane.scroll_offset, self.pane.cursor_line));
+            match rows_above {
+                Some(rows) if rows >= margin && rows + bottom_margin < visible_height => {}
+                Some(rows) if rows >= margin => {
+                    self.pane.scroll_offset = self.pane.lines_above(self.pane.cursor_line, visible_height - 1 - bottom_margin);
+                }
+                _ => self.pane.scroll_offset = self.pane.lines_above(self.pane.cursor_line, margin),
+            }
+        }
+
+        // 折り返し表示時は表示行単位でカーソルが収まるまでスクロール（横スクロールなし）
+        if self.wrapping() {
+            if visible_height > 0 {
+                if let Some((mut row, _)) = self.visual_position(self.pane.cursor_line, self.pane.cursor_col) {
+                    while row + bottom_margin >= visible_height && self.pane.scroll_offset < self.pane.cursor_line {
+                        row -= self.line_row_starts(self.pane.scroll_offset).len();
+                        self.pane.scroll_offset = self.pane.next_visible_line(self.pane.scroll_offset);
+                    }
+                }
+            }
+            self.pane.horizontal_scroll = 0;
+            return;
+        }
+
+        // 横スクロール
+        let visible_width = self.pane.editor_area.width.saturating_sub(2) as usize;
+        if visible_width > 0 {
+            if self.pane.cursor_col < self.pane.horizontal_scroll {
+                self.pane.horizontal_scroll = self.pane.cursor_col;
+            } else if self.pane.cursor_col >= self.pane.horizontal_scroll + visible_width {
+                self.pane.horizontal_scroll = self.pane.cursor_col.saturating_sub(visible_width) + 1;
+            }
+        }
+    }
+
+    /// マウスホイールでエディタを縦スクロール（directionが負なら上、正なら下にscroll_lines行）
+    pub(crate) fn handle_editor_scroll(&mut self, direction: i16) {
+        self.pane.follow_cursor = false; // マウススクロール中はカーソル追従を無効化
+        let total_lines = self.pane.buffer.len_lines();
+        let visible_height = self.pane.editor_area.height.saturating_sub(2) as usize;
+        let max_scroll = total_lines.saturating_sub(visible_height);
+
+        if direction < 0 {
+            self.pane.scroll_offset = self.pane.lines_above(self.pane.scroll_offset, self.scroll_lines);
+        } else {
+            self.pane.scroll_offset = self.pane.lines_below(self.pane.scroll_offset, self.scroll_lines).min(max_scroll);
+        }
+    }
+
+    /// マウスホイールでエディタを横スクロール（directionが負なら左、正なら右にscroll_columns桁）
+    pub(crate) fn handle_editor_horizontal_scroll(&mut self, direction: i16) {
+        if self.wrapping() {
+            return;
+        }
+        self.pane.follow_cursor = false; // マウススクロール中はカーソル追従を無効化
+        let visible_width = self.pane.editor_area.width.saturating_sub(2) as usize;
+        let ln_width = self.line_number_width();
+        let content_width = visible_width.saturating_sub(ln_width);
+        let max_scroll = self.pane.max_line_width.saturating_sub(content_width);
+
+        if direction < 0 {
+            self.pane.horizontal_scroll = self.pane.horizontal_scroll.saturating_sub(self.scroll_columns);
+        } else {
+            self.pane.horizontal_scroll = (self.pane.horizontal_scroll + self.scroll_columns).min(max_scroll);
+        }
+    }
+
+    pub(crate) fn handle_sidebar_click(&mut self, x: u16, y: u16) {
+        if x >= self.sidebar_area.x
+            && x < self.sidebar_area.x + self.sidebar_area.width
+            && y > self.sidebar_area.y
+            && y < self.sidebar_area.y + self.sidebar_area.height.saturating_sub(1)
+        {
+            // サイドバークリック時にディレクトリ内容を更新（外部変更の反映）
+            self.refresh_directory();
+
+            let visible_index = (y - self.sidebar_area.y - 1) as usize;
+            self.open_sidebar_item(visible_index + self.sidebar_scroll);
+        }
+    }
+
+    /// サイドバーに表示する行（絞り込み中は名前に文字列を含むエントリのみ）
+    pub(crate) fn sidebar_items(&self) -> Vec<SidebarItem> {
+        let filter = self.sidebar_filter.to_lowercase();
+        let multiple = self.roots.len() > 1;
+        let mut items = Vec::new();
+        for (i, root) in self.roots.iter().enumerate() {
+            if multiple {
+                items.push(SidebarItem::Root(i));
+                if root.collapsed {
+                    continue;
+                }
+            }
+            if root.current_dir != root.path {
+                items.push(SidebarItem::Parent(i));
+            }
+            items.extend(root.entries.iter()
+                .filter(|path| {
+                    filter.is_empty()
+                        || path.file_name().is_some_and(|n| n.to_string_lossy().to_lowercase().contains(&filter))
+                })
+                .map(|path| SidebarItem::Entry(i, path.clone())));
+        }
+        items
+    }
+
+    /// サイドバーの項目数（".." とルートの見出しを含む）
+    pub(crate) fn sidebar_item_count(&self) -> usize {
+        self.sidebar_items().len()
+    }
+
+    /// サイドバーの行の表示文字列（widthはサイズを右寄せする幅）
+    pub(crate) fn sidebar_item_label(&self, item: &SidebarItem, width: usize) -> String {
+        let indent = if self.roots.len() > 1 { "  " } else { "" };
+        match item {
+            SidebarItem::Root(i) => {
+                // 見出しには折りたたみ状態とルート以下の表示中のディレクトリを表示
+                let root = &self.roots[*i];
+                let marker = if root.collapsed { "▸" } else { "▾" };
+                match root.current_dir.strip_prefix(&root.path) {
+                    Ok(rel) if !rel.as_os_str().is_empty() => format!("{} {}/{}", marker, root.name(), rel.display()),
+                    _ => format!("{} {}", marker, root.name()),
+                }
+            }
+            SidebarItem::Parent(_) => format!("{}..", indent),
+            SidebarItem::Entry(_, path) => {
+                let name = path.file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                if let Some(target) = self.entry_links.get(path) {
+                    // シンボリックリンクはリンク先を表示（リンク切れは末尾に "!"）
+                    let slash = if path.is_dir() { "/" } else { "" };
+                    let broken = if path.exists() { "" } else { " !" };
+                    format!("{}{}{} → {}{}", indent, name, slash, target.display(), broken)
+                } else if path.is_dir() {
+                    format!("{}{}/", indent, name)
+                } else if let Some(&size) = self.entry_sizes.get(path) {
+                    // 幅に余裕があればサイズを右寄せ
+                    let size = format_file_size(size);
+                    let padding = width.saturating_sub(indent.len() + name.width() + size.len()).max(1);
+                    format!("{}{}{}{}", indent, name, " ".repeat(padding), size)
+                } else {
+                    format!("{}{}", indent, name)
+                }
+            }
+        }
+    }
+
+    /// サイドバーの項目を開く（indexは ".." とルートの見出しを含めた位置）
+    pub(crate) fn open_sidebar_item(&mut self, index: usize) {
+        let Some(item) = self.sidebar_items().get(index).cloned() else {
+            return;
+        };
+        self.active_root = item.root();
+        match item {
+            SidebarItem::Root(i) => self.roots[i].collapsed = !self.roots[i].collapsed,
+            SidebarItem::Parent(i) => {
+                if let Some(parent) = self.roots[i].current_dir.parent() {
+                    self.change_sidebar_dir(i, parent.to_path_buf());
+                }
+            }
+            SidebarItem::Entry(i, path) => {
+                if path.is_dir() {
+                    self.change_sidebar_dir(i, path);
+                } else {
+                    self.sidebar_focused = false;
+                    self.record_jump();
+                    self.open_file(&path);
+                }
+            }
+        }
+    }
+
+    /// サイドバーのディレクトリを移動（絞り込みは解除）
+    pub(crate) fn change_sidebar_dir(&mut self, root: usize, dir: PathBuf) {
+        self.roots[root].entries = Self::read_dir(&dir);
+        self.roots[root].current_dir = dir;
+        self.load_entry_info();
+        self.sidebar_filter.clear();
+        // 移動したルートの先頭を選択
+        let items = self.sidebar_items();
+        let first = items.iter().position(|item| item.root() == root).unwrap_or(0);
+        let visible_height = self.sidebar_area.height.saturating_sub(2) as usize;
+        self.sidebar_selected = first;
+        self.sidebar_scroll = first.min(items.len().saturating_sub(visible_height));
+        self.sidebar_scroll_x = 0;
+    }
+
+    /// ワークスペースにフォルダを追加（相対パスはサイドバーのディレクトリ基準）
+    pub(crate) fn add_workspace_folder(&mut self, input: &str) {
+        if input.is_empty() {
+            return;
+        }
+        let path = self.current_dir().join(input);
+        let path = path.canonicalize().unwrap_or(path);
+        if !path.is_dir() {
+            self.set_status_message(format!("{} is not a directory", path.display()));
+            return;
+        }
+        if let Some(index) = self.roots.iter().position(|r| r.path == path) {
+            self.active_root = index;
+            return;
+        }
+        self.roots.push(WorkspaceRoot::new(path));
+        self.active_root = self.roots.len() - 1;
+        self.load_entry_info();
+        if !self.sidebar_visible {
+            self.sidebar_visible = true;
+            self.needs_clear = true;
+        }
+    }
+
+    /// 操作中のルートフォルダをワークスペースから外す（最後の1つは外さない）
+    pub(crate) fn remove_workspace_folder(&mut self) {
+        if self.roots.len() <= 1 {
+            self.set_status_message("Cannot remove the last workspace folder");
+            return;
+        }
+        self.roots.remove(self.active_root);
+        self.active_root = self.active_root.min(self.roots.len() - 1);
+        self.load_entry_info();
+        self.sidebar_selected = 0;
+        self.sidebar_scroll = 0;
+        self.sidebar_scroll_x = 0;
+    }
+
+    /// サイドバーにフォーカスがあるときのキー入力を処理（処理した場合はtrue）
+    pub(crate) fn handle_sidebar_key(&mut self, key: &KeyEvent) -> bool {
+        if key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SUPER) {
+            return false;
+        }
+        match key.code {
+            KeyCode::Esc => {
+                // 絞り込み中なら解除、そうでなければエディタに戻る
+                if self.sidebar_filter.is_empty() {
+                    self.sidebar_focused = false;
+                } else {
+                    self.sidebar_filter.clear();
+                    self.sidebar_selected = 0;
+                }
+            }
+            KeyCode::Up => self.sidebar_selected = self.sidebar_selected.saturating_sub(1),
+            KeyCode::Down => {
+                self.sidebar_selected = (self.sidebar_selected + 1).min(self.sidebar_item_count().saturating_sub(1));
+            }
+            KeyCode::Enter => self.open_sidebar_item(self.sidebar_selected),
+            KeyCode::Backspace => {
+                self.sidebar_filter.pop();
+                self.sidebar_selected = 0;
+            }
+            KeyCode::Char(c) => {
+                self.sidebar_filter.push(c);
+                self.sidebar_selected = 0;
+            }
+            _ => return false,
+        }
+        // 選択中の項目のルートを操作対象にする
+        if let Some(item) = self.sidebar_items().get(self.sidebar_selected) {
+            self.active_root = item.root();
+        }
+        // 選択中の項目が見えるようにスクロール
+        let visible_height = self.sidebar_area.height.saturating_sub(2) as usize;
+        if self.sidebar_selected < self.sidebar_scroll {
+            self.sidebar_scroll = self.sidebar_selected;
+        } else if visible_height > 0 && self.sidebar_selected >= self.sidebar_scroll + visible_height {
+            self.sidebar_scroll = self.sidebar_selected + 1 - visible_height;
+        }
+        self.sidebar_scroll = self.sidebar_scroll.min(self.sidebar_item_count().saturating_sub(1));
+        true
+    }
+
+    /// 現在のディレクトリ内容を再読み込み
+    pub(crate) fn refresh_directory(&mut self) {
+        for root in self.roots.iter_mut() {
+            let new_entries = Self::read_dir(&root.current_dir);
+            if new_entries != root.entries {
+                root.entries = new_entries;
+            }
+        }
+        // サイズは内容の変更でも変わるので毎回読み直す
+        self.load_entry_info();
+    }
+
+    pub(crate) fn handle_sidebar_scroll(&mut self, x: u16, y: u16, direction: i16) {
+        if x >= self.sidebar_area.x
+            && x < self.sidebar_area.x + self.sidebar_area.width
+            && y >= self.sidebar_area.y
+            && y < self.sidebar_area.y + self.sidebar_area.height
+        {
+            let total_items = self.sidebar_item_count();
+            let visible_height = self.sidebar_area.height.saturating_sub(2) as usize;
+            let max_scroll = total_items.saturating_sub(visible_height);
+
+            if direction < 0 {
+                // Scroll up
+                self.sidebar_scroll = self.sidebar_scroll.saturating_sub(self.scroll_lines);
+            } else {
+                // Scroll down
+                self.sidebar_scroll = (self.sidebar_scroll + self.scroll_lines).min(max_scroll);
+            }
+        }
+    }
+
+    pub(crate) fn handle_sidebar_horizontal_scroll(&mut self, x: u16, y: u16, direction: i16) {
+        if x >= self.sidebar_area.x
+            && x < self.sidebar_area.x + self.sidebar_area.width
+            && y >= self.sidebar_area.y
+            && y < self.sidebar_area.y + self.sidebar_area.height
+        {
+            // 項目の最大文字幅を計算
+            let max_entry_width = self.sidebar_items().iter()
+                .map(|item| self.sidebar_item_label(item, 0).chars().count())
+                .max()
+                .unwrap_or(0);
+
+            let visible_width = self.sidebar_area.width.saturating_sub(2) as usize; // ボーダー分を引く
+            let max_scroll = max_entry_width.saturating_sub(visible_width);
+
+            if direction < 0 {
+                // Scroll left
+                self.sidebar_scroll_x = self.sidebar_scroll_x.saturating_sub(self.scroll_columns);
+            } else {
+                // Scroll right
+                self.sidebar_scroll_x = (self.sidebar_scroll_x + self.scroll_columns).min(max_scroll);
+            }
+        }
+    }
+
+    /// サイドバーとエディタの境界（サイドバーの右枠とエディタの左枠）上か
+    pub(crate) fn is_on_sidebar_border(&self, x: u16, y: u16) -> bool {
+        if !self.sidebar_visible || self.sidebar_area.width == 0 {
+            return false;
+        }
+        let border_x = self.sidebar_area.x + self.sidebar_area.width - 1;
+        (x == border_x || x == border_x + 1)
+            && y >= self.sidebar_area.y
+            && y < self.sidebar_area.y + self.sidebar_area.height
+    }
+
+    /// ドラッグ位置に合わせてサイドバーの幅を変更
+    pub(crate) fn resize_sidebar_to(&mut self, x: u16, screen_width: u16) {
+        if screen_width == 0 {
+            return;
+        }
+        // 境界の列がドラッグ位置に来る幅（%）
+        let percent = ((x as u32 + 1) * 100 / screen_width as u32) as u16;
+        let percent = percent.clamp(SIDEBAR_WIDTH_MIN_PERCENT, SIDEBAR_WIDTH_MAX_PERCENT);
+        if percent != self.sidebar_width_percent {
+            self.sidebar_width_percent = percent;
+            self.needs_clear = true;
+        }
+    }
+
+    /// サイドバーの幅の変更を終えて保存
+    pub(crate) fn finish_sidebar_resize(&mut self) {
+        self.sidebar_resizing = false;
+        save_sidebar_width(self.sidebar_width_percent);
+    }
+
+    /// ミニマップの中（枠を除く）かどうか
+    pub(crate) fn is_in_minimap(&self, x: u16, y: u16) -> bool {
+        let area = self.minimap_area;
+        x > area.x && x < area.x + area.width.saturating_sub(1)
+            && y > area.y && y < area.y + area.height.saturating_sub(1)
+    }
+
+    /// ミニマップの指定した行が表す位置がエディタの中央に来るようにスクロール
+    pub(crate) fn scroll_to_minimap_row(&mut self, y: u16) {
+        let row = y.saturating_sub(self.minimap_area.y + 1)
+            .min(self.minimap_area.height.saturating_sub(3)) as usize;
+        let line = row * self.minimap_lines_per_row();
+        let total_lines = self.pane.buffer.len_lines();
+        let visible_height = self.pane.editor_area.height.saturating_sub(2) as usize;
+        self.pane.follow_cursor = false; // マウススクロールと同様にカーソル追従を無効化
+        self.pane.scroll_offset = line.saturating_sub(visible_height / 2)
+            .min(total_lines.saturating_sub(visible_height));
+    }
+
+    pub(crate) fn handle_editor_click(&mut self, x: u16, y: u16) {
+        self.completion = None;
+        self.spell_suggestion = None;
+        self.sidebar_focused = false;
+        self.pane.cursors.clear();
+        let ln_width = self.line_number_width() as u16;
+        // エディタ領域内（ボーダー除く）かつ有効な行をクリックした場合
+        if x > self.pane.editor_area.x
+            && x < self.pane.editor_area.x + self.pane.editor_area.width - 1
+            && y > self.pane.editor_area.y
+            && y < self.pane.editor_area.y + self.pane.editor_area.height - 1
+        {
+            self.pane.follow_cursor = true;
+            let clicked_row = (y - self.pane.editor_area.y - 1) as usize;
+
+            // 行番号の右の折りたたみの印のクリックで展開
+            let on_fold_marker = x == self.pane.editor_area.x + ln_width;
+
+            if self.wrapping() {
+                // 行番号領域のクリックは表示行の先頭として扱う
+                let display_col = x.saturating_sub(self.pane.editor_area.x + 1 + ln_width) as usize;
+                if let Some((line, col)) = self.wrapped_screen_to_pos(clicked_row, display_col) {
+                    if on_fold_marker && col == 0 {
+                        self.pane.remove_fold(line);
+                    }
+                    self.pane.cursor_line = line;
+                    self.pane.cursor_col = col;
+                }
+                return;
+            }
+
+            let clicked_line = self.pane.lines_below(self.pane.scroll_offset, clicked_row);
+            if self.pane.visible_rows_between(self.pane.scroll_offset, clicked_line) == clicked_row {
+                if on_fold_marker {
+                    self.pane.remove_fold(clicked_line);
+                }
+                self.pane.cursor_line = clicked_line;
+                // 行番号領域をクリックした場合は行頭に移動
+                if x < self.pane.editor_area.x + 1 + ln_width {
+                    self.pane.cursor_col = 0;
+                } else {
+                    // クリック位置（表示幅）から文字インデックスに変換
+                    let clicked_display_col = (x - self.pane.editor_area.x - 1 - ln_width) as usize;
+                    self.pane.cursor_col = self.content_col_to_char_col(clicked_line, clicked_display_col);
+                }
+            }
+        }
+    }
+
+    /// エディタ領域内の座標を行・列に変換
+    pub(crate) fn screen_to_editor_pos(&self, x: u16, y: u16) -> Option<(usize, usize)> {
+        let ln_width = self.line_number_width() as u16;
+        if x >= self.pane.editor_area.x + 1 + ln_width
+            && x < self.pane.editor_area.x + self.pane.editor_area.width - 1
+            && y > self.pane.editor_area.y
+            && y < self.pane.editor_area.y + self.pane.editor_area.height - 1
+        {
+            if self.wrapping() {
+                let display_col = (x - self.pane.editor_area.x - 1 - ln_width) as usize;
+                return self.wrapped_screen_to_pos((y - self.pane.editor_area.y - 1) as usize, display_col);
+            }
+            let row = (y - self.pane.editor_area.y - 1) as usize;
+            let line = self.pane.lines_below(self.pane.scroll_offset, row);
+            if self.pane.visible_rows_between(self.pane.scroll_offset, line) == row {
+                let clicked_display_col = (x - self.pane.editor_area.x - 1 - ln_width) as usize;
+                let col = self.content_col_to_char_col(line, clicked_display_col);
+                return Some((line, col));
+            }
+        }
+        None
+    }
+
+    /// OSC 52でクリップボードにコピー
+    pub(crate) fn copy_to_clipboard_osc52(&self, text: &str) {
+        use std::io::Write;
+        let encoded = base64_encode(text.as_bytes());
+        // OSC 52: システムクリップボードにコピー
+        // \x1b]52;c;<base64>\x07
+        let osc52 = format!("\x1b]52;c;{}\x07", encoded);
+        let _ = std::io::stdout().write_all(osc52.as_bytes());
+        let _ = std::io::stdout().flush();
+    }
+
+    /// 折り返し表示の切り替え
+    pub(crate) fn toggle_wrap_lines(&mut self) {
+        self.wrap_lines = !self.wrap_lines;
+        self.pane.horizontal_scroll = 0;
+        self.pane.follow_cursor = true;
+    }
+
+    /// キー・マウスなどのイベントを処理（終了する場合はtrue）
+    pub fn handle_event(&mut self, event: Event) -> bool {
+        let should_break = match event {
+            Event::Key(key) => self.handle_key(key),
+            Event::Mouse(mouse) => {
+                let x = mouse.column;
+                let y = mouse.row;
+                // もう一方のペインをクリックしたらフォーカスを移す
+                if mouse.kind == MouseEventKind::Down(MouseButton::Left) {
+                    let in_other_pane = self.other_pane.as_ref().is_some_and(|p| {
+                        x >= p.editor_area.x
+                            && x < p.editor_area.x + p.editor_area.width
+                            && y >= p.editor_area.y
+                            && y < p.editor_area.y + p.editor_area.height
+                    });
+                    if in_other_pane {
+                        self.switch_pane();
+                    }
+                }
+                let in_sidebar = x >= self.sidebar_area.x
+                    && x < self.sidebar_area.x + self.sidebar_area.width
+                    && y >= self.sidebar_area.y
+                    && y < self.sidebar_area.y + self.sidebar_area.height;
+                let in_editor = x >= self.pane.editor_area.x
+                    && x < self.pane.editor_area.x + self.pane.editor_area.width
+                    && y >= self.pane.editor_area.y
+                    && y < self.pane.editor_area.y + self.pane.editor_area.height;
+
+                // ターミナルをクリックしたらフォーカス、他をクリックしたら外す
+                if mouse.kind == MouseEventKind::Down(MouseButton::Left) && self.terminal_visible {
+                    let area = self.terminal_area;
+                    self.terminal_focused = x >= area.x
+                        && x < area.x + area.width
+                        && y >= area.y
+                        && y < area.y + area.height;
+                }
+
+                match mouse.kind {
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        // コピーボタンのクリック判定
+                        let clicked_copy_button = if let Some(btn_area) = self.copy_button_area {
+                            x >= btn_area.x && x < btn_area.x + btn_area.width
+                                && y >= btn_area.y && y < btn_area.y + btn_area.height
+                        } else {
+                            false
+                        };
+
+                        if self.is_on_sidebar_border(x, y) {
+                            // 境界のドラッグでサイドバーの幅を変更
+                            self.sidebar_resizing = true;
+                        } else if self.is_in_minimap(x, y) {
+                            // ミニマップのクリック・ドラッグでその位置へスクロール
+                            self.minimap_dragging = true;
+                            self.scroll_to_minimap_row(y);
+                        } else if clicked_copy_button {
+                            // コピーボタンクリック：OSC 52でコピーして選択解除
+                            if let Some(text) = self.get_selected_text() {
+                                self.copy_to_clipboard_osc52(&text);
+                            }
+                            self.clear_selection();
+                        } else {
+                            self.handle_tab_click(x, y);
+                            self.handle_sidebar_click(x, y);
+                            // エディタ領域でのクリックは選択開始
+                            if in_editor {
+                                // 既存の選択を解除
+                                self.clear_selection();
+                                // クリック位置にカーソル移動
+                                self.handle_editor_click(x, y);
+                                // 選択開始
+                                if let Some((line, col)) = self.screen_to_editor_pos(x, y) {
+                                    self.start_selection(line, col);
+                                }
+                            } else {
+                                self.clear_selection();
+                            }
+                        }
+                    }
+                    MouseEventKind::Drag(MouseButton::Left) if self.sidebar_resizing => {
+                        let (screen_width, _) = crossterm::terminal::size().unwrap_or((80, 24));
+                        self.resize_sidebar_to(x, screen_width);
+                    }
+                    MouseEventKind::Drag(MouseButton::Left) if self.minimap_dragging => {
+                        self.scroll_to_minimap_row(y);
+                    }
+                    // エディタ領域でのドラッグは選択範囲を更新
+                    MouseEventKind::Drag(MouseButton::Left) if in_editor && self.is_selecting => {
+                        if let Some((line, col)) = self.screen_to_editor_pos(x, y) {
+                            self.update_selection(line, col);
+                            // カーソルも移動
+                            self.pane.cursor_line = line;
+                            self.pane.cursor_col = col;
+                        }
+                    }
+                    MouseEventKind::Up(MouseButton::Left) if self.sidebar_resizing => {
+                        self.finish_sidebar_resize();
+                    }
+                    MouseEventKind::Up(MouseButton::Left) if self.minimap_dragging => {
+                        self.minimap_dragging = false;
+                    }
+                    MouseEventKind::Up(MouseButton::Left) => {
+                        // 選択終了
+                        self.end_selection();
+                    }
+                    MouseEventKind::ScrollUp => {
+                        if in_sidebar {
+                            self.handle_sidebar_scroll(x, y, -1);
+                        } else if in_editor && self.diff_view.is_some() {
+                            let visible_height = self.pane.editor_area.height.saturating_sub(2) as usize;
+                            if let Some(diff) = self.diff_view.as_mut() {
+                                diff.scroll_by(-3, visible_height);
+                            }
+                        } else if in_editor && self.pane.is_image_mode {
+                            self.zoom_image(IMAGE_ZOOM_STEP);
+                        } else if in_editor {
+                            self.handle_editor_scroll(-1);
+                        }
+                    }
+                    MouseEventKind::ScrollDown => {
+                        if in_sidebar {
+                            self.handle_sidebar_scroll(x, y, 1);
+                        } else if in_editor && self.diff_view.is_some() {
+                            let visible_height = self.pane.editor_area.height.saturating_sub(2) as usize;
+                            if let Some(diff) = self.diff_view.as_mut() {
+                                diff.scroll_by(3, visible_height);
+                            }
+                        } else if in_editor && self.pane.is_image_mode {
+                            self.zoom_image(1.0 / IMAGE_ZOOM_STEP);
+                        } else if in_editor {
+                            self.handle_editor_scroll(1);
+                        }
+                    }
+                    MouseEventKind::ScrollLeft => {
+                        if in_sidebar {
+                            self.handle_sidebar_horizontal_scroll(x, y, -1);
+                        } else if in_editor {
+                            self.handle_editor_horizontal_scroll(-1);
+                        }
+                    }
+                    MouseEventKind::ScrollRight => {
+                        if in_sidebar {
+                            self.handle_sidebar_horizontal_scroll(x, y, 1);
+                        } else if in_editor {
+                            self.handle_editor_horizontal_scroll(1);
+                        }
+                    }
+                    _ => {}
+                }
+                false
+            }
+            Event::Paste(text) => {
+                // ターミナルにフォーカス中ならシェルに送る
+                if self.terminal_focused {
+                    if let Some(terminal) = self.terminal.as_mut() {
+                        terminal.send_text(&text);
+                    }
+                    return false;
+                }
+                // ペーストされたテキストを挿入
+                self.clear_selection();
+                for c in text.chars() {
+                    self.insert_char(c);
+                }
+                false
+            }
+            Event::Resize(_, _) => {
+                // ターミナルリサイズ時に画面をクリア
+                self.needs_clear = true;
+
+                // 画像モードの場合は画像状態をリセット（再レンダリング用）
+                if self.pane.is_image_mode {
+                    self.image_state = None;
+                    if let Some(path) = self.pane.file_path.clone() {
+                        self.request_image_decode(path);
+                        self.image_loading = Some(Instant::now());
+                    }
+                }
+
+                // カーソル行の調整
+                let total_lines = self.pane.buffer.len_lines();
+                if self.pane.cursor_line >= total_lines {
+                    self.pane.cursor_line = total_lines.saturating_sub(1);
+                }
+                // カーソル列の調整
+                self.clamp_cursor_col();
+                // 垂直スクロールの調整
+                if self.pane.scroll_offset > total_lines.saturating_sub(1) {
+                    self.pane.scroll_offset = total_lines.saturating_sub(1);
+                }
+                // 水平スクロールの調整
+                if self.pane.horizontal_scroll > self.pane.max_line_width {
+                    self.pane.horizontal_scroll = 0;
+                }
+                // サイドバーの垂直スクロール調整
+                let total_items = self.sidebar_item_count();
+                if self.sidebar_scroll >= total_items {
+                    self.sidebar_scroll = total_items.saturating_sub(1);
+                }
+                // サイドバーの水平スクロールをリセット
+                self.sidebar_scroll_x = 0;
+                false
+            }
+            _ => false,
+        };
+        self.update_mark_region();
+        should_break
+    }
+
+    /// 描画の前に画像の読み込みやターミナルの出力などの結果を反映
+    pub fn tick(&mut self) {
+        // 画像デコード完了イベントを受け取る
+        self.receive_decoded_image();
+
+        // ファイル検索で見つかったファイルを受け取る
+        self.receive_found_files();
+
+        // 画像リサイズ完了イベントを受け取る
+        if let Ok(protocol) = self.image_rx.try_recv() {
+            if let Some(ref mut state) = self.image_state {
+                state.set_protocol(protocol);
+            }
+        }
+
+        self.poll_terminal();
+        self.expire_status_message();
+        self.update_scroll();
+    }
+
+    /// 画面クリアが必要ならtrue（呼ぶとリセットされる）
+    pub fn take_needs_clear(&mut self) -> bool {
+        std::mem::take(&mut self.needs_clear)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::config::ColorsConfig;
+
+    /// 端末や設定ファイルを使わずに、textを開いた状態のAppを作る
+    pub(crate) fn test_app(text: &str) -> App {
+        let colors = UiColors::new(&ColorsConfig::default(), false);
+        let mut app = App::with_config(Vec::new(), Config::default(), Picker::from_fontsize((8, 12)), colors);
+        app.pane.file_path = Some(PathBuf::from("test.txt"));
+        app.pane.buffer = Rope::from_str(text);
+        app
+    }
+
+    #[test]
+    fn jump_back_after_next_match() {
+        let mut app = test_app("foo\nbar\nfoo\nbaz\nfoo\n");
+        app.pane.cursor_line = 1;
+        app.pane.cursor_col = 2;
+        app.search_query = "foo".to_string();
+        app.search();
+        app.jump_to_match();
+        assert_eq!((app.pane.cursor_line, app.pane.cursor_col), (2, 0));
+        app.next_match();
+        assert_eq!(app.pane.cursor_line, 4);
+        app.jump_back();
+        assert_eq!((app.pane.cursor_line, app.pane.cursor_col), (2, 0));
+    }
+}
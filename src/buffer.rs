@@ -0,0 +1,1568 @@
+//! テキストバッファとカーソル移動・編集操作
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::SystemTime;
+
+use ratatui::{
+    layout::Rect,
+    style::Color,
+};
+use ropey::Rope;
+use unicode_width::UnicodeWidthChar;
+
+use crate::app::{App, CommandKind};
+use crate::highlight::Language;
+
+/// 1文字の表示幅（タブは4、全角は2）
+pub(crate) fn char_display_width(ch: char) -> usize {
+    if ch == '\t' {
+        4
+    } else {
+        ch.width().unwrap_or(1)
+    }
+}
+
+/// 折り返し表示時に各表示行が始まる文字インデックスを計算
+pub(crate) fn wrap_row_starts(chars: impl Iterator<Item = char>, width: usize) -> Vec<usize> {
+    let mut starts = vec![0];
+    if width == 0 {
+        return starts;
+    }
+    let mut row_width = 0;
+    for (i, ch) in chars.enumerate() {
+        if ch == '\n' {
+            break;
+        }
+        let ch_width = char_display_width(ch);
+        if row_width > 0 && row_width + ch_width > width {
+            starts.push(i);
+            row_width = 0;
+        }
+        row_width += ch_width;
+    }
+    starts
+}
+
+/// 閉じタグが不要なHTMLの要素
+pub(crate) const HTML_VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr",
+];
+
+/// `>` を入力した直後の行頭からカーソルまでのテキストが開始タグで終わっていれば、対応する閉じタグを返す
+pub(crate) fn html_closing_tag(before_cursor: &str) -> Option<String> {
+    let tag = before_cursor.strip_suffix('>')?;
+    let tag = &tag[tag.rfind('<')? + 1..];
+    // 閉じタグ・コメント・宣言・自己終了タグは対象外
+    if tag.contains('>') || tag.ends_with('/') || !tag.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let name: String = tag.chars().take_while(|c| c.is_ascii_alphanumeric() || *c == '-').collect();
+    if HTML_VOID_ELEMENTS.contains(&name.to_ascii_lowercase().as_str()) {
+        return None;
+    }
+    Some(format!("</{}>", name))
+}
+
+/// Markdownのリスト項目で改行したときの動作
+#[derive(Debug, PartialEq)]
+pub(crate) enum ListContinuation {
+    /// 次の行に挿入する項目の記号（インデント込み）
+    Next(String),
+    /// 空の項目なのでリストを終える
+    End,
+}
+
+/// Markdownの行がリスト項目（`-`, `*`, `+`, `1.`, `1)`、チェックボックス付きも含む）なら改行時の動作を返す
+pub(crate) fn markdown_list_continuation(line: &str) -> Option<ListContinuation> {
+    let content = line.trim_start();
+    let indent = &line[..line.len() - content.len()];
+    let digits = content.chars().take_while(|c| c.is_ascii_digit()).count();
+    let (marker, rest) = if digits > 0 {
+        let delimiter = content[digits..].chars().next().filter(|c| matches!(c, '.' | ')'))?;
+        let number: usize = content[..digits].parse().ok()?;
+        (format!("{}{}", number + 1, delimiter), &content[digits + 1..])
+    } else {
+        let bullet = content.chars().next().filter(|c| matches!(c, '-' | '*' | '+'))?;
+        (bullet.to_string(), &content[1..])
+    };
+    // 記号の後には空白が必要（`**強調**` などを除外）
+    let text = rest.strip_prefix([' ', '\t'])?.trim_start();
+    let (checkbox, text) = match text.get(..3) {
+        Some("[ ]" | "[x]" | "[X]") => (" [ ]", text[3..].trim_start()),
+        _ => ("", text),
+    };
+    if text.is_empty() {
+        return Some(ListContinuation::End);
+    }
+    Some(ListContinuation::Next(format!("{}{}{} ", indent, marker, checkbox)))
+}
+
+/// 追加のカーソル（複数カーソルで同時に編集する位置）
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct Cursor {
+    pub(crate) line: usize,
+    pub(crate) col: usize,
+}
+
+/// テキスト選択範囲を表す構造体
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Selection {
+    /// 選択開始位置（行、列）
+    pub(crate) start: (usize, usize),
+    /// 選択終了位置（行、列）
+    pub(crate) end: (usize, usize),
+}
+
+impl Selection {
+    pub(crate) fn new(line: usize, col: usize) -> Self {
+        Self {
+            start: (line, col),
+            end: (line, col),
+        }
+    }
+
+    /// 正規化された範囲を取得（startが常にendより前になるように）
+    pub(crate) fn normalized(&self) -> ((usize, usize), (usize, usize)) {
+        if self.start.0 < self.end.0 || (self.start.0 == self.end.0 && self.start.1 <= self.end.1) {
+            (self.start, self.end)
+        } else {
+            (self.end, self.start)
+        }
+    }
+
+    /// 指定位置が選択範囲内かどうか
+    pub(crate) fn contains(&self, line: usize, col: usize) -> bool {
+        let ((start_line, start_col), (end_line, end_col)) = self.normalized();
+        if line < start_line || line > end_line {
+            return false;
+        }
+        if line == start_line && line == end_line {
+            col >= start_col && col < end_col
+        } else if line == start_line {
+            col >= start_col
+        } else if line == end_line {
+            col < end_col
+        } else {
+            true
+        }
+    }
+}
+
+/// キルリングに保持する最大件数
+pub(crate) const KILL_RING_MAX: usize = 60;
+
+/// 補完対象の単語を構成する文字か
+pub(crate) fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// JSON/TOML/YAMLを整形（パースできなければエラーメッセージを返す）
+pub(crate) fn format_document(language: Language, source: &str) -> Result<String, String> {
+    // エラーメッセージは1行目だけをステータスバーに表示
+    let first_line = |e: String| e.lines().next().unwrap_or_default().to_string();
+    let formatted = match language {
+        Language::Json => {
+            let value: serde_json::Value = serde_json::from_str(source)
+                .map_err(|e| first_line(format!("JSON: {}", e)))?;
+            serde_json::to_string_pretty(&value).map_err(|e| first_line(e.to_string()))?
+        }
+        Language::Toml => {
+            let value: toml::Table = toml::from_str(source).map_err(|e| first_line(e.to_string()))?;
+            toml::to_string_pretty(&value).map_err(|e| first_line(e.to_string()))?
+        }
+        Language::Yaml => {
+            let value: serde_yaml::Value = serde_yaml::from_str(source)
+                .map_err(|e| first_line(format!("YAML: {}", e)))?;
+            serde_yaml::to_string(&value).map_err(|e| first_line(e.to_string()))?
+        }
+        _ => return Err("Format is only available for JSON, TOML and YAML".to_string()),
+    };
+    if formatted.ends_with('\n') {
+        Ok(formatted)
+    } else {
+        Ok(formatted + "\n")
+    }
+}
+
+/// 外部フォーマッタに標準入力でテキストを渡し、標準出力を返す（失敗時は標準エラー出力の1行目）
+pub(crate) fn run_formatter(command: &str, path: &Path, source: &str) -> Result<String, String> {
+    // "{file}" はファイルパスに置き換える（prettier --stdin-filepath {file} など）
+    let file = path.to_string_lossy();
+    let mut args = command.split_whitespace().map(|arg| arg.replace("{file}", &file));
+    let program = args.next().ok_or_else(|| "Formatter command is empty".to_string())?;
+    let mut cmd = Command::new(&program);
+    cmd.args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(dir) = path.parent() {
+        cmd.current_dir(dir);
+    }
+    let mut child = cmd.spawn().map_err(|e| format!("{}: {}", program, e))?;
+
+    // 出力が詰まらないよう、書き込みは別スレッドで行う
+    let mut stdin = child.stdin.take().ok_or_else(|| format!("{}: failed to open stdin", program))?;
+    let input = source.to_string();
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output().map_err(|e| format!("{}: {}", program, e))?;
+    let _ = writer.join();
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match stderr.lines().find(|line| !line.trim().is_empty()) {
+            Some(line) => format!("{}: {}", program, line.trim()),
+            None => format!("{}: {}", program, output.status),
+        });
+    }
+    String::from_utf8(output.stdout).map_err(|_| format!("{}: output is not valid UTF-8", program))
+}
+
+/// エディタのペイン（表示中のファイルとカーソル・スクロール位置）
+pub(crate) struct EditorPane {
+    pub(crate) file_path: Option<PathBuf>,
+    pub(crate) buffer: Rope,
+    // 保存済みの内容（比較用）
+    pub(crate) saved_content: String,
+    // ファイルの更新日時（外部変更検知用）
+    pub(crate) file_modified_time: Option<SystemTime>,
+    pub(crate) cursor_line: usize,
+    pub(crate) cursor_col: usize,
+    // 主カーソル以外のカーソル（追加した順）
+    pub(crate) cursors: Vec<Cursor>,
+    // 上下移動で保つ列と、それを記録したときのカーソル位置（カーソルが別の操作で動いていれば使わない）
+    pub(crate) goal_col: Option<(usize, (usize, usize))>,
+    pub(crate) scroll_offset: usize,
+    pub(crate) horizontal_scroll: usize,
+    // カーソル追従を有効にするか
+    pub(crate) follow_cursor: bool,
+    // 現在のファイルの言語
+    pub(crate) current_language: Option<Language>,
+    pub(crate) is_image_mode: bool,
+    // ANSIのエスケープシーケンスを解釈して色付きで表示するか（ログなど）
+    pub(crate) ansi_colors: bool,
+    pub(crate) editor_area: Rect,
+    // ブックマークした行（昇順）
+    pub(crate) bookmarks: Vec<usize>,
+    // 折りたたんだ範囲（表示する開始行, 隠す最後の行）、開始行の昇順で重ならない
+    pub(crate) folds: Vec<(usize, usize)>,
+    // ファイルのあるリポジトリのブランチ名
+    pub(crate) git_branch: Option<String>,
+    // キャッシュ
+    pub(crate) source_cache: String,
+    pub(crate) highlight_cache: Option<Vec<Color>>,
+    // 辞書にない単語の範囲（バイト位置の昇順、スペルチェックが無効なら空）
+    pub(crate) spell_errors: Vec<(usize, usize)>,
+    pub(crate) buffer_dirty: bool,
+    // 行オフセットキャッシュ（バイト位置）
+    pub(crate) line_offsets: Vec<usize>,
+    // 最大行幅キャッシュ（文字数）
+    pub(crate) max_line_width: usize,
+}
+
+impl EditorPane {
+    /// 編集に合わせてブックマークの行をずらす（lineより後ろのremoved行を削除し、added行を追加）
+    pub(crate) fn adjust_bookmarks(&mut self, line: usize, removed: usize, added: usize) {
+        if self.bookmarks.is_empty() || (removed == 0 && added == 0) {
+            return;
+        }
+        for bookmark in self.bookmarks.iter_mut() {
+            if *bookmark > line {
+                *bookmark = if *bookmark <= line + removed {
+                    line
+                } else {
+                    *bookmark - removed + added
+                };
+            }
+        }
+        self.bookmarks.dedup();
+    }
+
+    /// 編集に合わせて折りたたみをずらす（折りたたんだ範囲で行が増減した場合は展開）
+    pub(crate) fn adjust_folds(&mut self, line: usize, removed: usize, added: usize) {
+        if self.folds.is_empty() || (removed == 0 && added == 0) {
+            return;
+        }
+        self.folds.retain(|&(start, end)| line > end || line + removed < start);
+        for (start, end) in self.folds.iter_mut() {
+            if *start > line {
+                *start = *start - removed + added;
+                *end = *end - removed + added;
+            }
+        }
+    }
+
+    /// 文字を挿入する前にブックマークと折りたたみを調整
+    pub(crate) fn before_insert(&mut self, char_idx: usize, text: &str) {
+        let added = text.chars().filter(|&c| c == '\n').count();
+        let line = self.buffer.char_to_line(char_idx);
+        self.adjust_bookmarks(line, 0, added);
+        self.adjust_folds(line, 0, added);
+    }
+
+    /// 文字を削除する前にブックマークと折りたたみを調整
+    pub(crate) fn before_remove(&mut self, start: usize, end: usize) {
+        let line = self.buffer.char_to_line(start);
+        let removed = self.buffer.char_to_line(end) - line;
+        self.adjust_bookmarks(line, removed, 0);
+        self.adjust_folds(line, removed, 0);
+    }
+
+    /// 行から始まるブロックの隠す最後の行（括弧が次の行以降で閉じていればその手前まで、
+    /// なければ字下げが深い行が続く範囲）
+    pub(crate) fn fold_range_at(&self, line: usize) -> Option<usize> {
+        if line >= self.buffer.len_lines() {
+            return None;
+        }
+        self.bracket_block_end(line).or_else(|| self.indent_block_end(line))
+    }
+
+    /// 行で閉じていない最初の括弧が閉じる行の手前（閉じ括弧の前に文字があればその行まで）
+    fn bracket_block_end(&self, line: usize) -> Option<usize> {
+        let mut depth = 0usize;
+        for line_idx in line..self.buffer.len_lines() {
+            let mut in_string = false;
+            let mut escaped = false;
+            let mut before_closer = true;
+            let mut prev = None;
+            for ch in self.buffer.line(line_idx).chars() {
+                if in_string {
+                    match ch {
+                        _ if escaped => escaped = false,
+                        '\\' => escaped = true,
+                        '"' => in_string = false,
+                        _ => {}
+                    }
+                } else {
+                    match ch {
+                        '"' => in_string = true,
+                        '/' if prev == Some('/') => break,
+                        '{' | '[' | '(' => depth += 1,
+                        // 最初の行の閉じていない閉じ括弧は前のブロックのもの
+                        '}' | ']' | ')' if depth > 0 => {
+                            depth -= 1;
+                            if depth == 0 && line_idx > line {
+                                let end = if before_closer { line_idx - 1 } else { line_idx };
+                                return (end > line).then_some(end);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                if !ch.is_whitespace() {
+                    before_closer = false;
+                }
+                prev = Some(ch);
+            }
+            if line_idx == line && depth == 0 {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// 行の字下げの幅（空行ならNone）
+    fn indent_width(&self, line: usize) -> Option<usize> {
+        let mut width = 0;
+        for ch in self.buffer.line(line).chars() {
+            match ch {
+                '\n' | '\r' => return None,
+                ' ' | '\t' => width += char_display_width(ch),
+                _ => return Some(width),
+            }
+        }
+        None
+    }
+
+    /// 行より字下げが深い行が続く範囲の最後の行（間の空行は含め、末尾の空行は含めない）
+    fn indent_block_end(&self, line: usize) -> Option<usize> {
+        let base = self.indent_width(line)?;
+        let mut end = None;
+        for line_idx in line + 1..self.buffer.len_lines() {
+            match self.indent_width(line_idx) {
+                None => continue,
+                Some(width) if width > base => end = Some(line_idx),
+                Some(_) => break,
+            }
+        }
+        end
+    }
+
+    /// 行から始まる範囲を折りたたむ（中にある折りたたみはまとめる）
+    pub(crate) fn add_fold(&mut self, start: usize, end: usize) {
+        let mut end = end;
+        self.folds.retain(|&(s, e)| {
+            let inside = s >= start && s <= end;
+            if inside {
+                end = end.max(e);
+            }
+            !inside
+        });
+        let idx = self.folds.partition_point(|&(s, _)| s < start);
+        self.folds.insert(idx, (start, end));
+    }
+
+    /// 行から始まる折りたたみを展開（なければfalse）
+    pub(crate) fn remove_fold(&mut self, start: usize) -> bool {
+        let len = self.folds.len();
+        self.folds.retain(|&(s, _)| s != start);
+        self.folds.len() != len
+    }
+
+    /// 行を隠している折りたたみを展開（検索や行ジャンプで隠れた行に移動したとき）
+    pub(crate) fn reveal_line(&mut self, line: usize) {
+        self.folds.retain(|&(start, end)| line <= start || line > end);
+    }
+
+    /// 行が折りたたみで隠れているか（折りたたみの開始行は表示される）
+    pub(crate) fn is_hidden(&self, line: usize) -> bool {
+        self.folds.iter().any(|&(start, end)| start < line && line <= end)
+    }
+
+    /// 行から始まる折りたたみで隠している最後の行
+    pub(crate) fn fold_end_at(&self, line: usize) -> Option<usize> {
+        self.folds.iter().find(|&&(start, _)| start == line).map(|&(_, end)| end)
+    }
+
+    /// 行が表示される位置の行（隠れている行は折りたたみの開始行）
+    pub(crate) fn visible_line(&self, line: usize) -> usize {
+        self.folds.iter()
+            .find(|&&(start, end)| start < line && line <= end)
+            .map_or(line, |&(start, _)| start)
+    }
+
+    /// 次に表示される行（折りたたんだ行の次は隠した範囲の次の行）
+    pub(crate) fn next_visible_line(&self, line: usize) -> usize {
+        self.fold_end_at(line).unwrap_or(line) + 1
+    }
+
+    /// 表示される行をn行上に数えた行（先頭で止まる）
+    pub(crate) fn lines_above(&self, line: usize, n: usize) -> usize {
+        let mut line = line;
+        for _ in 0..n {
+            if line == 0 {
+                break;
+            }
+            line = self.visible_line(line - 1);
+        }
+        line
+    }
+
+    /// 表示される行をn行下に数えた行（最後の行で止まる）
+    pub(crate) fn lines_below(&self, line: usize, n: usize) -> usize {
+        let mut line = line;
+        for _ in 0..n {
+            let next = self.next_visible_line(line);
+            if next >= self.buffer.len_lines() {
+                break;
+            }
+            line = next;
+        }
+        line
+    }
+
+    /// fromからtoの手前までに表示される行数
+    pub(crate) fn visible_rows_between(&self, from: usize, to: usize) -> usize {
+        let hidden: usize = self.folds.iter()
+            .map(|&(start, end)| (end + 1).min(to).saturating_sub((start + 1).max(from)))
+            .sum();
+        to.saturating_sub(from) - hidden
+    }
+
+    /// 行の文字数（末尾の改行を除く、CRLFの `\r` も含めない）
+    pub(crate) fn line_len(&self, line_idx: usize) -> usize {
+        if line_idx >= self.buffer.len_lines() {
+            return 0;
+        }
+        let line = self.buffer.line(line_idx);
+        let mut len = line.len_chars();
+        if len > 0 && line.char(len - 1) == '\n' {
+            len -= 1;
+        }
+        if len > 0 && line.char(len - 1) == '\r' {
+            len -= 1;
+        }
+        len
+    }
+
+    pub(crate) fn current_line_len(&self) -> usize {
+        self.line_len(self.cursor_line)
+    }
+
+    /// 表示幅から文字インデックスを計算（全角は2、タブは4として数える）
+    pub(crate) fn display_col_to_char_col(&self, line_idx: usize, display_col: usize) -> usize {
+        if line_idx >= self.buffer.len_lines() {
+            return 0;
+        }
+        let mut current_width = 0;
+        let mut char_col = 0;
+        for ch in self.buffer.line(line_idx).chars().take(self.line_len(line_idx)) {
+            let ch_width = char_display_width(ch);
+            if current_width + ch_width > display_col {
+                break;
+            }
+            current_width += ch_width;
+            char_col += 1;
+        }
+        char_col
+    }
+
+    pub(crate) fn clamp_cursor_col(&mut self) {
+        self.cursor_col = self.cursor_col.min(self.current_line_len());
+    }
+
+    /// 行・列から文字インデックスを計算（列は行末で切り詰め）
+    pub(crate) fn pos_to_char_idx(&self, line: usize, col: usize) -> usize {
+        if line >= self.buffer.len_lines() {
+            return self.buffer.len_chars();
+        }
+        self.buffer.line_to_char(line) + col.min(self.line_len(line))
+    }
+
+    /// 文字インデックスから行・列を計算（バッファ末尾を超える場合は末尾）
+    pub(crate) fn char_idx_to_pos(&self, idx: usize) -> (usize, usize) {
+        let idx = idx.min(self.buffer.len_chars());
+        let line = self.buffer.char_to_line(idx);
+        (line, idx - self.buffer.line_to_char(line))
+    }
+
+    /// 文字インデックスの位置にカーソルを移動
+    pub(crate) fn char_idx_to_cursor(&mut self, idx: usize) {
+        (self.cursor_line, self.cursor_col) = self.char_idx_to_pos(idx);
+    }
+
+    /// カーソル位置の文字インデックス
+    pub(crate) fn cursor_to_char_idx(&self) -> usize {
+        self.pos_to_char_idx(self.cursor_line, self.cursor_col)
+    }
+
+    /// 主カーソルと追加のカーソルの全てで同じ操作を行う（後ろのカーソルから順に行い、
+    /// 編集で増減した文字数だけ処理済みの後ろのカーソルをずらす。重なったカーソルはまとめる）
+    pub(crate) fn for_each_cursor(&mut self, mut op: impl FnMut(&mut Self)) {
+        if self.cursors.is_empty() {
+            op(self);
+            return;
+        }
+        let mut positions: Vec<(usize, bool)> = self.cursors.iter()
+            .map(|c| (self.pos_to_char_idx(c.line, c.col), false))
+            .collect();
+        positions.push((self.cursor_to_char_idx(), true));
+        // 同じ位置のカーソルは主カーソルを残す
+        positions.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)));
+        positions.dedup_by_key(|p| p.0);
+
+        let mut done: Vec<(usize, bool)> = Vec::with_capacity(positions.len());
+        for (idx, is_primary) in positions {
+            self.char_idx_to_cursor(idx);
+            let len_before = self.buffer.len_chars();
+            op(self);
+            let delta = self.buffer.len_chars() as isize - len_before as isize;
+            for (later, _) in done.iter_mut() {
+                *later = later.saturating_add_signed(delta);
+            }
+            done.push((self.cursor_to_char_idx(), is_primary));
+        }
+
+        let primary = done.iter().find(|(_, is_primary)| *is_primary).map_or(0, |&(idx, _)| idx);
+        self.char_idx_to_cursor(primary);
+        let mut cursors: Vec<Cursor> = Vec::new();
+        for &(idx, _) in done.iter().rev() {
+            let (line, col) = self.char_idx_to_pos(idx);
+            let cursor = Cursor { line, col };
+            if idx != primary && !cursors.contains(&cursor) {
+                cursors.push(cursor);
+            }
+        }
+        self.cursors = cursors;
+    }
+
+    /// 追加のカーソルがその位置にあるか
+    pub(crate) fn has_cursor_at(&self, line: usize, col: usize) -> bool {
+        self.cursors.iter().any(|c| c.line == line && c.col == col)
+    }
+
+    /// 指定したバイト位置が辞書にない単語の中か
+    pub(crate) fn is_misspelled(&self, byte_pos: usize) -> bool {
+        let i = self.spell_errors.partition_point(|&(_, end)| end <= byte_pos);
+        self.spell_errors.get(i).is_some_and(|&(start, _)| start <= byte_pos)
+    }
+
+    /// 上下移動の目標の列（直前の上下移動からカーソルが動いていなければ、そのとき保った列）
+    fn goal_column(&self) -> usize {
+        match self.goal_col {
+            Some((goal, pos)) if pos == (self.cursor_line, self.cursor_col) => goal,
+            _ => self.cursor_col,
+        }
+    }
+
+    /// 目標の列を保ったまま指定行へ移動（短い行では行末に寄せる）
+    fn move_vertically_to(&mut self, line: usize) {
+        let goal = self.goal_column();
+        self.cursor_line = line;
+        self.cursor_col = goal.min(self.current_line_len());
+        self.goal_col = Some((goal, (self.cursor_line, self.cursor_col)));
+    }
+
+    pub(crate) fn move_up(&mut self, count: usize) {
+        self.follow_cursor = true;
+        if self.cursor_line > 0 {
+            self.move_vertically_to(self.lines_above(self.cursor_line, count));
+        }
+    }
+
+    pub(crate) fn move_down(&mut self, count: usize) {
+        self.follow_cursor = true;
+        let line = self.lines_below(self.cursor_line, count);
+        if line != self.cursor_line {
+            self.move_vertically_to(line);
+        }
+    }
+
+    pub(crate) fn move_left(&mut self, count: usize) {
+        self.follow_cursor = true;
+        self.goal_col = None;
+        for _ in 0..count {
+            if self.cursor_col > 0 {
+                self.cursor_col -= 1;
+            } else if self.cursor_line > 0 {
+                self.cursor_line = self.visible_line(self.cursor_line - 1);
+                self.cursor_col = self.current_line_len();
+            } else {
+                break;
+            }
+        }
+    }
+
+    pub(crate) fn move_right(&mut self, count: usize) {
+        self.follow_cursor = true;
+        self.goal_col = None;
+        for _ in 0..count {
+            if self.cursor_col < self.current_line_len() {
+                self.cursor_col += 1;
+            } else if self.next_visible_line(self.cursor_line) < self.buffer.len_lines() {
+                self.cursor_line = self.next_visible_line(self.cursor_line);
+                self.cursor_col = 0;
+            } else {
+                break;
+            }
+        }
+    }
+
+    pub(crate) fn move_to_line_start(&mut self) {
+        self.follow_cursor = true;
+        self.goal_col = None;
+        self.cursor_col = 0;
+    }
+
+    pub(crate) fn move_to_line_end(&mut self) {
+        self.follow_cursor = true;
+        self.goal_col = None;
+        self.cursor_col = self.current_line_len();
+    }
+
+    pub(crate) fn insert_char(&mut self, c: char) {
+        self.follow_cursor = true;
+        self.goal_col = None;
+        let idx = self.cursor_to_char_idx();
+        if c == '\n' {
+            self.before_insert(idx, "\n");
+        }
+        self.buffer.insert_char(idx, c);
+        self.buffer_dirty = true;
+        if c == '\n' {
+            self.cursor_line += 1;
+            self.cursor_col = 0;
+        } else {
+            self.cursor_col += 1;
+        }
+    }
+
+    /// カーソルの前の1文字を削除（CRLFはまとめて削除して行を結合）
+    pub(crate) fn delete_backward(&mut self) {
+        self.follow_cursor = true;
+        self.goal_col = None;
+        let idx = self.cursor_to_char_idx();
+        if idx == 0 {
+            return;
+        }
+        let start = if self.buffer.char(idx - 1) == '\n' && idx >= 2 && self.buffer.char(idx - 2) == '\r' {
+            idx - 2
+        } else {
+            idx - 1
+        };
+        self.before_remove(start, idx);
+        self.buffer.remove(start..idx);
+        self.buffer_dirty = true;
+        self.char_idx_to_cursor(start);
+    }
+
+    /// カーソル位置の1文字を削除（行末のCRLFはまとめて削除して次の行を結合）
+    pub(crate) fn delete_forward(&mut self) {
+        self.follow_cursor = true;
+        self.goal_col = None;
+        let idx = self.cursor_to_char_idx();
+        if idx >= self.buffer.len_chars() {
+            return;
+        }
+        let end = self.char_end(idx);
+        self.before_remove(idx, end);
+        self.buffer.remove(idx..end);
+        self.buffer_dirty = true;
+    }
+
+    /// idxの1文字の直後の位置（CRLFの `\r` では `\n` の後ろ）
+    fn char_end(&self, idx: usize) -> usize {
+        if self.buffer.get_char(idx) == Some('\r') && self.buffer.get_char(idx + 1) == Some('\n') {
+            idx + 2
+        } else {
+            idx + 1
+        }
+    }
+
+    /// カーソル前後の文字を入れ替えてカーソルを進める（行末では直前の2文字を入れ替える）
+    pub(crate) fn transpose_chars(&mut self) {
+        let mut idx = self.cursor_to_char_idx();
+        if idx == self.buffer.len_chars() || self.char_end(idx) > idx + 1 || self.buffer.char(idx) == '\n' {
+            idx = idx.saturating_sub(1);
+        }
+        if idx == 0 {
+            return;
+        }
+        self.follow_cursor = true;
+        let swapped: String = [self.buffer.char(idx), self.buffer.char(idx - 1)].iter().collect();
+        self.buffer.remove(idx - 1..idx + 1);
+        self.buffer.insert(idx - 1, &swapped);
+        self.buffer_dirty = true;
+        self.char_idx_to_cursor(idx + 1);
+    }
+
+    /// kill_lineで削除する範囲（数引数があればその行数分を改行ごと、行末では改行を、それ以外は行末まで）
+    pub(crate) fn kill_line_range(&self, count: Option<usize>) -> Option<(usize, usize)> {
+        let start = self.cursor_to_char_idx();
+        let len_chars = self.buffer.len_chars();
+        let end = match count {
+            Some(count) => {
+                let end_line = self.cursor_line + count;
+                if end_line < self.buffer.len_lines() {
+                    self.buffer.line_to_char(end_line)
+                } else {
+                    len_chars
+                }
+            }
+            None if self.cursor_col >= self.current_line_len() => self.char_end(start).min(len_chars),
+            None => self.buffer.line_to_char(self.cursor_line) + self.current_line_len(),
+        };
+        (start < end).then_some((start, end))
+    }
+}
+
+impl Default for EditorPane {
+    fn default() -> Self {
+        Self {
+            file_path: None,
+            buffer: Rope::new(),
+            saved_content: String::new(),
+            file_modified_time: None,
+            cursor_line: 0,
+            cursor_col: 0,
+            scroll_offset: 0,
+            horizontal_scroll: 0,
+            follow_cursor: true,
+            current_language: None,
+            is_image_mode: false,
+            ansi_colors: false,
+            editor_area: Rect::default(),
+            bookmarks: Vec::new(),
+            folds: Vec::new(),
+            cursors: Vec::new(),
+            git_branch: None,
+            source_cache: String::new(),
+            highlight_cache: None,
+            spell_errors: Vec::new(),
+            buffer_dirty: false,
+            line_offsets: Vec::new(),
+            max_line_width: 0,
+            goal_col: None,
+        }
+    }
+}
+
+impl App {
+    /// 編集中のテキスト
+    pub fn text(&self) -> String {
+        self.pane.buffer.to_string()
+    }
+
+    /// カーソル位置（行、列）
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.pane.cursor_line, self.pane.cursor_col)
+    }
+
+    pub(crate) fn current_line_len(&self) -> usize {
+        self.pane.current_line_len()
+    }
+
+    /// カーソルの列を行の長さに収める
+    pub(crate) fn clamp_cursor_col(&mut self) {
+        self.pane.clamp_cursor_col();
+    }
+
+    /// 行・列から文字インデックスを計算（列は行末で切り詰め）
+    pub fn pos_to_char_idx(&self, line: usize, col: usize) -> usize {
+        self.pane.pos_to_char_idx(line, col)
+    }
+
+    /// 文字インデックスから行・列を計算（バッファ末尾を超える場合は末尾）
+    pub fn char_idx_to_pos(&self, idx: usize) -> (usize, usize) {
+        self.pane.char_idx_to_pos(idx)
+    }
+
+    /// 文字インデックスの位置にカーソルを移動
+    pub(crate) fn char_idx_to_cursor(&mut self, idx: usize) {
+        self.pane.char_idx_to_cursor(idx);
+    }
+
+    /// カーソル位置の文字インデックス
+    pub fn cursor_to_char_idx(&self) -> usize {
+        self.pane.cursor_to_char_idx()
+    }
+
+    pub fn move_up(&mut self, count: usize) {
+        self.pane.for_each_cursor(|pane| pane.move_up(count));
+    }
+
+    pub fn move_down(&mut self, count: usize) {
+        self.pane.for_each_cursor(|pane| pane.move_down(count));
+    }
+
+    pub fn move_left(&mut self, count: usize) {
+        self.pane.for_each_cursor(|pane| pane.move_left(count));
+    }
+
+    pub fn move_right(&mut self, count: usize) {
+        self.pane.for_each_cursor(|pane| pane.move_right(count));
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        self.add_to_tabs();
+        self.pane.for_each_cursor(|pane| pane.insert_char(c));
+    }
+
+    /// 入力された文字を挿入（HTMLでは開始タグの `>` の後に閉じタグを補う）
+    pub(crate) fn insert_typed_char(&mut self, c: char) {
+        self.insert_char(c);
+        // 複数カーソルでは補完しない
+        if !self.smart_insert || c != '>' || self.pane.current_language != Some(Language::Html) || !self.pane.cursors.is_empty() {
+            return;
+        }
+        let line_start = self.pane.buffer.line_to_char(self.pane.cursor_line);
+        let before_cursor: String = self.pane.buffer.slice(line_start..self.cursor_to_char_idx()).into();
+        if let Some(tag) = html_closing_tag(&before_cursor) {
+            // カーソルは開始タグと閉じタグの間に残す
+            let idx = self.cursor_to_char_idx();
+            self.pane.before_insert(idx, &tag);
+            self.pane.buffer.insert(idx, &tag);
+        }
+    }
+
+    /// 改行を挿入（Markdownのリスト項目では次の項目の記号を補い、空の項目ではリストを終える）
+    pub fn insert_newline(&mut self) {
+        if !self.smart_insert || self.pane.current_language != Some(Language::Markdown) || !self.pane.cursors.is_empty() {
+            self.insert_char('\n');
+            return;
+        }
+        let line_start = self.pane.buffer.line_to_char(self.pane.cursor_line);
+        let line: String = self.pane.buffer.line(self.pane.cursor_line).chars().take_while(|&c| c != '\n').collect();
+        match markdown_list_continuation(&line) {
+            Some(ListContinuation::Next(marker)) => {
+                self.insert_char('\n');
+                let idx = self.cursor_to_char_idx();
+                self.pane.before_insert(idx, &marker);
+                self.pane.buffer.insert(idx, &marker);
+                self.pane.cursor_col += marker.chars().count();
+            }
+            Some(ListContinuation::End) => {
+                // 空の項目は記号を消してリストを終える
+                self.add_to_tabs();
+                let line_end = line_start + line.chars().count();
+                self.pane.before_remove(line_start, line_end);
+                self.pane.buffer.remove(line_start..line_end);
+                self.pane.buffer_dirty = true;
+                self.pane.cursor_col = 0;
+            }
+            None => self.insert_char('\n'),
+        }
+    }
+
+    pub fn delete_char_backspace(&mut self) {
+        self.add_to_tabs();
+        self.pane.for_each_cursor(EditorPane::delete_backward);
+    }
+
+    pub fn delete_char_delete(&mut self) {
+        self.add_to_tabs();
+        self.pane.for_each_cursor(EditorPane::delete_forward);
+    }
+
+    /// カーソル前後の文字を入れ替えてカーソルを進める（行末では直前の2文字を入れ替える）
+    pub fn transpose_chars(&mut self) {
+        self.add_to_tabs();
+        self.pane.transpose_chars();
+    }
+
+    pub fn move_to_line_start(&mut self) {
+        self.pane.for_each_cursor(EditorPane::move_to_line_start);
+    }
+
+    pub fn move_to_line_end(&mut self) {
+        self.pane.for_each_cursor(EditorPane::move_to_line_end);
+    }
+
+    /// カーソル位置の単語が次に出てくる位置（単語内の同じ位置）にカーソルを追加（末尾まで探したら先頭から）
+    pub(crate) fn add_cursor_at_next_match(&mut self) {
+        if self.pane.is_image_mode {
+            return;
+        }
+        let cursor = self.cursor_to_char_idx();
+        let buffer = &self.pane.buffer;
+        let mut start = cursor;
+        while start > 0 && is_word_char(buffer.char(start - 1)) {
+            start -= 1;
+        }
+        let mut end = cursor;
+        while end < buffer.len_chars() && is_word_char(buffer.char(end)) {
+            end += 1;
+        }
+        if start == end {
+            self.set_status_message("No word at cursor");
+            return;
+        }
+        let word: String = buffer.slice(start..end).into();
+        let offset = cursor - start;
+
+        // 単語全体が一致する位置（文字インデックス）
+        self.update_cache();
+        let source = &self.pane.source_cache;
+        let occurrences: Vec<usize> = source.match_indices(&word)
+            .filter(|&(pos, _)| {
+                let before = source[..pos].chars().next_back();
+                let after = source[pos + word.len()..].chars().next();
+                !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+            })
+            .map(|(pos, _)| self.pane.buffer.byte_to_char(pos) + offset)
+            .collect();
+
+        // 最後に追加したカーソルより後ろ、なければ先頭から探す
+        let last = self.pane.cursors.last()
+            .map_or(cursor, |c| self.pane.pos_to_char_idx(c.line, c.col));
+        let taken = |idx: usize| {
+            idx == cursor || self.pane.cursors.iter().any(|c| self.pane.pos_to_char_idx(c.line, c.col) == idx)
+        };
+        let next = occurrences.iter().find(|&&idx| idx > last && !taken(idx))
+            .or_else(|| occurrences.iter().find(|&&idx| !taken(idx)));
+        match next {
+            Some(&idx) => {
+                let (line, col) = self.char_idx_to_pos(idx);
+                self.pane.cursors.push(Cursor { line, col });
+                self.set_status_message(format!("{} cursors", self.pane.cursors.len() + 1));
+            }
+            None => self.set_status_message("No more occurrences"),
+        }
+    }
+
+    /// 行末まで削除（数引数があればその行数分を改行ごと削除）
+    pub fn kill_line(&mut self, count: Option<usize>) {
+        self.add_to_tabs();
+        self.pane.follow_cursor = true;
+        if let Some((start_idx, end_idx)) = self.pane.kill_line_range(count) {
+            self.kill_range(start_idx, end_idx);
+        }
+    }
+
+    /// 指定範囲を削除してキルリングに追加
+    pub(crate) fn kill_range(&mut self, start_idx: usize, end_idx: usize) {
+        let text = self.pane.buffer.slice(start_idx..end_idx).to_string();
+        self.pane.before_remove(start_idx, end_idx);
+        self.pane.buffer.remove(start_idx..end_idx);
+        self.pane.buffer_dirty = true;
+        self.push_kill(text);
+    }
+
+    /// キルリングに追加（直前のコマンドもkillなら末尾に追記）
+    pub(crate) fn push_kill(&mut self, text: String) {
+        self.this_command = CommandKind::Kill;
+        if text.is_empty() {
+            return;
+        }
+        if self.last_command == CommandKind::Kill {
+            if let Some(last) = self.kill_ring.last_mut() {
+                last.push_str(&text);
+                return;
+            }
+        }
+        self.kill_ring.push(text);
+        if self.kill_ring.len() > KILL_RING_MAX {
+            self.kill_ring.remove(0);
+        }
+    }
+
+    /// マークを設定（Ctrl-Space）
+    pub fn set_mark(&mut self) {
+        self.clear_selection();
+        self.selection = Some(Selection::new(self.pane.cursor_line, self.pane.cursor_col));
+        self.mark_active = true;
+    }
+
+    /// マーク設定中なら選択範囲の終端をカーソル位置に合わせる
+    pub(crate) fn update_mark_region(&mut self) {
+        if !self.mark_active {
+            return;
+        }
+        if let Some(ref mut sel) = self.selection {
+            sel.end = (self.pane.cursor_line, self.pane.cursor_col);
+        }
+    }
+
+    /// 選択範囲の文字インデックス範囲
+    pub(crate) fn selection_char_range(&self) -> Option<(usize, usize)> {
+        let ((start_line, start_col), (end_line, end_col)) = self.selection?.normalized();
+        let start = self.pos_to_char_idx(start_line, start_col);
+        let end = self.pos_to_char_idx(end_line, end_col);
+        if start < end {
+            Some((start, end))
+        } else {
+            None
+        }
+    }
+
+    /// 選択範囲を削除してキルリングに追加（Ctrl-W）
+    pub fn kill_region(&mut self) {
+        if let Some((start, end)) = self.selection_char_range() {
+            self.add_to_tabs();
+            self.pane.follow_cursor = true;
+            let text = self.pane.buffer.slice(start..end).to_string();
+            self.copy_to_clipboard_osc52(&text);
+            self.kill_range(start, end);
+            self.char_idx_to_cursor(start);
+        }
+        self.clear_selection();
+    }
+
+    /// 選択範囲をキルリングにコピー（Alt-W）
+    pub fn copy_region(&mut self) {
+        if let Some((start, end)) = self.selection_char_range() {
+            let text = self.pane.buffer.slice(start..end).to_string();
+            self.copy_to_clipboard_osc52(&text);
+            self.push_kill(text);
+        }
+        self.clear_selection();
+    }
+
+    /// 最新のキルをカーソル位置に挿入（Ctrl-Y）
+    pub fn yank(&mut self) {
+        if let Some(text) = self.kill_ring.last().cloned() {
+            self.kill_ring_index = self.kill_ring.len() - 1;
+            self.insert_yank(&text);
+        }
+    }
+
+    /// 直前のヤンクを古いキルに置き換え（Alt-Y）
+    pub fn yank_pop(&mut self) {
+        if self.last_command != CommandKind::Yank || self.kill_ring.is_empty() {
+            return;
+        }
+        if let Some((start, end)) = self.last_yank {
+            self.pane.before_remove(start, end);
+            self.pane.buffer.remove(start..end);
+            self.char_idx_to_cursor(start);
+            self.kill_ring_index = if self.kill_ring_index == 0 {
+                self.kill_ring.len() - 1
+            } else {
+                self.kill_ring_index - 1
+            };
+            let text = self.kill_ring[self.kill_ring_index].clone();
+            self.insert_yank(&text);
+        }
+    }
+
+    pub(crate) fn insert_yank(&mut self, text: &str) {
+        self.add_to_tabs();
+        self.pane.follow_cursor = true;
+        let idx = self.cursor_to_char_idx();
+        self.pane.before_insert(idx, text);
+        self.pane.buffer.insert(idx, text);
+        self.pane.buffer_dirty = true;
+        let end = idx + text.chars().count();
+        self.char_idx_to_cursor(end);
+        self.last_yank = Some((idx, end));
+        self.this_command = CommandKind::Yank;
+    }
+
+    /// バッファ全体を置き換え（カーソルと選択範囲は文字インデックスで、ブックマークは行番号でなるべく維持）
+    pub(crate) fn replace_buffer(&mut self, text: &str) {
+        if text == self.pane.source_cache {
+            return;
+        }
+        self.add_to_tabs();
+        let cursor = self.cursor_to_char_idx();
+        let selection = self.selection.map(|sel| {
+            (self.pos_to_char_idx(sel.start.0, sel.start.1), self.pos_to_char_idx(sel.end.0, sel.end.1))
+        });
+        self.pane.buffer = Rope::from_str(text);
+        self.pane.buffer_dirty = true;
+        let last_line = self.pane.buffer.len_lines().saturating_sub(1);
+        self.pane.bookmarks.retain(|&line| line <= last_line);
+        // 行の構成が変わるので折りたたみは解除
+        self.pane.folds.clear();
+        self.char_idx_to_cursor(cursor);
+        self.clamp_cursor_col();
+        if let Some((start, end)) = selection {
+            self.selection = Some(Selection { start: self.char_idx_to_pos(start), end: self.char_idx_to_pos(end) });
+        }
+        self.pane.follow_cursor = true;
+    }
+
+    /// 選択開始
+    pub(crate) fn start_selection(&mut self, line: usize, col: usize) {
+        self.selection = Some(Selection::new(line, col));
+        self.is_selecting = true;
+    }
+
+    /// 選択更新
+    pub(crate) fn update_selection(&mut self, line: usize, col: usize) {
+        if let Some(ref mut sel) = self.selection {
+            sel.end = (line, col);
+        }
+    }
+
+    /// 選択終了
+    pub(crate) fn end_selection(&mut self) {
+        self.is_selecting = false;
+        // 選択範囲が空なら選択解除
+        if let Some(sel) = self.selection {
+            if sel.start == sel.end {
+                self.selection = None;
+                self.copy_button_area = None;
+            } else {
+                // コピーボタンの位置を計算（選択終端の右側）
+                self.update_copy_button_position();
+            }
+        }
+    }
+
+    /// コピーボタンの位置を更新
+    pub(crate) fn update_copy_button_position(&mut self) {
+        if let Some(sel) = self.selection {
+            let (_, (end_line, end_col)) = sel.normalized();
+            let ln_width = self.line_number_width();
+
+            // 画面上の位置を計算（表示幅・折り返しを考慮）
+            if let Some((screen_line, display_col)) = self.visual_position(end_line, end_col) {
+                let screen_y = self.pane.editor_area.y + 1 + screen_line as u16;
+                let screen_x = self.pane.editor_area.x + 1 + ln_width as u16 + display_col as u16;
+
+                // ボタンサイズ: [Copy]
+                let button_width = 6u16;
+                let button_height = 1u16;
+
+                // 画面内に収まるように調整
+                let x = screen_x.min(self.pane.editor_area.x + self.pane.editor_area.width - button_width - 1);
+                let y = screen_y.min(self.pane.editor_area.y + self.pane.editor_area.height - button_height - 1);
+
+                self.copy_button_area = Some(Rect::new(x, y, button_width, button_height));
+            } else {
+                self.copy_button_area = None;
+            }
+        } else {
+            self.copy_button_area = None;
+        }
+    }
+
+    /// 選択解除
+    pub(crate) fn clear_selection(&mut self) {
+        // コピーボタンが表示されていた場合は画面クリアが必要
+        if self.copy_button_area.is_some() {
+            self.needs_clear = true;
+        }
+        self.selection = None;
+        self.copy_button_area = None;
+        self.is_selecting = false;
+        self.mark_active = false;
+    }
+
+    /// マーク設定中でなければ選択解除（マーク設定中の移動は選択範囲を広げる）
+    pub(crate) fn clear_selection_unless_mark(&mut self) {
+        if !self.mark_active {
+            self.clear_selection();
+        }
+    }
+
+    /// 選択範囲のテキストを取得
+    pub(crate) fn get_selected_text(&self) -> Option<String> {
+        let sel = self.selection?;
+        let ((start_line, start_col), (end_line, end_col)) = sel.normalized();
+
+        let mut result = String::new();
+        for line_idx in start_line..=end_line {
+            if line_idx >= self.pane.buffer.len_lines() {
+                break;
+            }
+            let line = self.pane.buffer.line(line_idx);
+            // Ropeyのline()は改行を含むので除去
+            let line_str: String = line.chars()
+                .filter(|&c| c != '\n' && c != '\r')
+                .collect();
+            let line_len = line_str.chars().count();
+
+            let start = if line_idx == start_line { start_col } else { 0 };
+            let end = if line_idx == end_line { end_col.min(line_len) } else { line_len };
+
+            if start <= line_len {
+                let chars: Vec<char> = line_str.chars().collect();
+                let actual_end = end.min(chars.len());
+                if start < actual_end {
+                    let slice: String = chars[start..actual_end].iter().collect();
+                    result.push_str(&slice);
+                }
+            }
+
+            // 最終行以外は改行を追加
+            if line_idx < end_line {
+                result.push('\n');
+            }
+        }
+
+        if result.is_empty() {
+            None
+        } else {
+            Some(result)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pane(text: &str) -> EditorPane {
+        EditorPane {
+            buffer: Rope::from_str(text),
+            ..Default::default()
+        }
+    }
+
+    fn pane_at(text: &str, line: usize, col: usize) -> EditorPane {
+        let mut pane = pane(text);
+        pane.cursor_line = line;
+        pane.cursor_col = col;
+        pane
+    }
+
+    #[test]
+    fn line_len_excludes_line_endings() {
+        let p = pane("abc\nあいう\r\n\nend");
+        assert_eq!(p.line_len(0), 3);
+        assert_eq!(p.line_len(1), 3);
+        assert_eq!(p.line_len(2), 0);
+        assert_eq!(p.line_len(3), 3);
+        assert_eq!(p.line_len(4), 0);
+    }
+
+    #[test]
+    fn empty_buffer() {
+        let mut p = pane("");
+        assert_eq!(p.buffer.len_lines(), 1);
+        assert_eq!(p.current_line_len(), 0);
+        assert_eq!(p.cursor_to_char_idx(), 0);
+        assert_eq!(p.char_idx_to_pos(10), (0, 0));
+        assert_eq!(p.display_col_to_char_col(0, 5), 0);
+        p.move_up(1);
+        p.move_down(1);
+        p.move_left(1);
+        p.move_right(1);
+        assert_eq!((p.cursor_line, p.cursor_col), (0, 0));
+        p.delete_backward();
+        p.delete_forward();
+        assert_eq!(p.buffer.to_string(), "");
+        assert!(!p.buffer_dirty);
+    }
+
+    #[test]
+    fn insert_into_empty_buffer() {
+        let mut p = pane("");
+        p.insert_char('日');
+        p.insert_char('\n');
+        p.insert_char('a');
+        assert_eq!(p.buffer.to_string(), "日\na");
+        assert_eq!((p.cursor_line, p.cursor_col), (1, 1));
+        assert!(p.buffer_dirty);
+    }
+
+    #[test]
+    fn display_col_with_full_width_chars() {
+        let p = pane("あいう\r\n");
+        assert_eq!(p.display_col_to_char_col(0, 0), 0);
+        assert_eq!(p.display_col_to_char_col(0, 1), 0);
+        assert_eq!(p.display_col_to_char_col(0, 2), 1);
+        assert_eq!(p.display_col_to_char_col(0, 5), 2);
+        // 行末より右をクリックしても改行文字の上には来ない
+        assert_eq!(p.display_col_to_char_col(0, 100), 3);
+        assert_eq!(p.display_col_to_char_col(5, 3), 0);
+    }
+
+    #[test]
+    fn display_col_with_tab() {
+        let p = pane("\tx");
+        assert_eq!(p.display_col_to_char_col(0, 3), 0);
+        assert_eq!(p.display_col_to_char_col(0, 4), 1);
+        assert_eq!(p.display_col_to_char_col(0, 5), 2);
+    }
+
+    #[test]
+    fn char_idx_round_trip() {
+        let p = pane("日本語\r\nab\n");
+        for (line, col) in [(0, 0), (0, 3), (1, 0), (1, 2), (2, 0)] {
+            let idx = p.pos_to_char_idx(line, col);
+            assert_eq!(p.char_idx_to_pos(idx), (line, col));
+        }
+        // 列は行末（\r の手前）で切り詰める
+        assert_eq!(p.pos_to_char_idx(0, 10), 3);
+        assert_eq!(p.pos_to_char_idx(9, 0), p.buffer.len_chars());
+    }
+
+    #[test]
+    fn cursor_idx_clamps_col() {
+        let p = pane_at("ab\r\ncd", 0, 5);
+        assert_eq!(p.cursor_to_char_idx(), 2);
+        let mut p = p;
+        p.clamp_cursor_col();
+        assert_eq!(p.cursor_col, 2);
+    }
+
+    #[test]
+    fn move_at_buffer_start() {
+        let mut p = pane_at("ab\ncd", 0, 0);
+        p.move_left(3);
+        assert_eq!((p.cursor_line, p.cursor_col), (0, 0));
+        p.move_up(2);
+        assert_eq!((p.cursor_line, p.cursor_col), (0, 0));
+    }
+
+    #[test]
+    fn move_at_buffer_end() {
+        let mut p = pane_at("ab\ncd", 1, 2);
+        p.move_right(3);
+        assert_eq!((p.cursor_line, p.cursor_col), (1, 2));
+        p.move_down(2);
+        assert_eq!((p.cursor_line, p.cursor_col), (1, 2));
+        // 末尾の改行の後ろの空行まで移動できる
+        let mut p = pane_at("ab\n", 0, 2);
+        p.move_right(1);
+        assert_eq!((p.cursor_line, p.cursor_col), (1, 0));
+    }
+
+    #[test]
+    fn move_across_crlf() {
+        let mut p = pane_at("あい\r\nう", 0, 2);
+        p.move_right(1);
+        assert_eq!((p.cursor_line, p.cursor_col), (1, 0));
+        p.move_left(1);
+        assert_eq!((p.cursor_line, p.cursor_col), (0, 2));
+        p.move_to_line_start();
+        assert_eq!(p.cursor_col, 0);
+        p.move_to_line_end();
+        assert_eq!(p.cursor_col, 2);
+    }
+
+    #[test]
+    fn kill_line_range_at_crlf() {
+        let p = pane_at("ab\r\ncd\r\n", 0, 2);
+        assert_eq!(p.kill_line_range(None), Some((2, 4)));
+        let p = pane_at("ab\r\ncd\r\n", 0, 1);
+        assert_eq!(p.kill_line_range(None), Some((1, 2)));
+        assert_eq!(p.kill_line_range(Some(1)), Some((1, 4)));
+        let p = pane_at("ab\r\ncd", 1, 2);
+        assert_eq!(p.kill_line_range(None), None);
+    }
+
+    #[test]
+    fn transpose_at_crlf_line_end() {
+        let mut p = pane_at("ab\r\ncd", 0, 2);
+        p.transpose_chars();
+        assert_eq!(p.buffer.to_string(), "ba\r\ncd");
+        assert_eq!((p.cursor_line, p.cursor_col), (0, 2));
+        let mut p = pane_at("abc", 0, 1);
+        p.transpose_chars();
+        assert_eq!(p.buffer.to_string(), "bac");
+        assert_eq!(p.cursor_col, 2);
+    }
+
+    #[test]
+    fn move_vertically_clamps_col() {
+        let mut p = pane_at("日本語です\nab\r\nxyz", 0, 5);
+        p.move_down(1);
+        assert_eq!((p.cursor_line, p.cursor_col), (1, 2));
+        p.move_down(5);
+        assert_eq!((p.cursor_line, p.cursor_col), (2, 3));
+        p.move_up(5);
+        assert_eq!((p.cursor_line, p.cursor_col), (0, 5));
+    }
+
+    #[test]
+    fn move_vertically_keeps_goal_col() {
+        let mut p = pane_at("abcdef\nab\n\nabcdefgh", 0, 5);
+        p.move_down(1);
+        assert_eq!((p.cursor_line, p.cursor_col), (1, 2));
+        p.move_down(1);
+        assert_eq!((p.cursor_line, p.cursor_col), (2, 0));
+        p.move_down(1);
+        assert_eq!((p.cursor_line, p.cursor_col), (3, 5));
+        p.move_up(3);
+        assert_eq!((p.cursor_line, p.cursor_col), (0, 5));
+    }
+
+    #[test]
+    fn horizontal_move_resets_goal_col() {
+        let mut p = pane_at("abcdef\nab\nabcdef", 0, 5);
+        p.move_down(1);
+        p.move_left(1);
+        p.move_right(1);
+        p.move_down(1);
+        assert_eq!((p.cursor_line, p.cursor_col), (2, 2));
+        // 上下移動以外でカーソルが動いた場合も記録した列は使わない
+        p.move_up(1);
+        p.cursor_col = 0;
+        p.move_down(1);
+        assert_eq!((p.cursor_line, p.cursor_col), (2, 0));
+    }
+
+    #[test]
+    fn fold_range_brackets() {
+        let p = pane("fn main() {\n    let s = \"{\";\n    foo(a,\n        b);\n}\n");
+        assert_eq!(p.fold_range_at(0), Some(3));
+        // 閉じ括弧の前に文字がある場合はその行まで隠す
+        assert_eq!(p.fold_range_at(2), Some(3));
+        assert_eq!(p.fold_range_at(1), None);
+        assert_eq!(p.fold_range_at(4), None);
+        // 閉じた括弧のあとで開いた括弧を使う
+        let p = pane("} else {\n    x\n}");
+        assert_eq!(p.fold_range_at(0), Some(1));
+    }
+
+    #[test]
+    fn fold_range_indent() {
+        let p = pane("def f():\n    a\n\n    b\n\nc\n");
+        assert_eq!(p.fold_range_at(0), Some(3));
+        assert_eq!(p.fold_range_at(1), None);
+        assert_eq!(p.fold_range_at(2), None);
+    }
+
+    #[test]
+    fn move_skips_folded_lines() {
+        let mut p = pane_at("a {\n  b\n  c\n}\nd", 0, 3);
+        p.add_fold(0, 2);
+        p.move_down(1);
+        assert_eq!((p.cursor_line, p.cursor_col), (3, 1));
+        p.move_up(1);
+        assert_eq!((p.cursor_line, p.cursor_col), (0, 3));
+        p.move_right(1);
+        assert_eq!((p.cursor_line, p.cursor_col), (3, 0));
+        p.move_left(1);
+        assert_eq!((p.cursor_line, p.cursor_col), (0, 3));
+        assert_eq!(p.visible_rows_between(0, 5), 3);
+        assert_eq!(p.lines_below(0, 10), 4);
+        assert_eq!(p.lines_above(4, 2), 0);
+    }
+
+    #[test]
+    fn edits_shift_or_open_folds() {
+        let mut p = pane_at("x\na {\n  b\n}\n", 0, 1);
+        p.add_fold(1, 2);
+        p.insert_char('\n');
+        assert_eq!(p.folds, vec![(2, 3)]);
+        // 折りたたみの開始行で改行すると展開
+        p.cursor_line = 2;
+        p.move_to_line_end();
+        p.insert_char('\n');
+        assert!(p.folds.is_empty());
+    }
+
+    #[test]
+    fn replace_buffer_clears_folds() {
+        let mut app = crate::app::tests::test_app("fn a() {\n    1\n}\nfn b() {\n    2\n}\n");
+        app.pane.add_fold(0, 2);
+        app.pane.cursor_line = 3;
+        app.pane.cursor_col = 4;
+        // 整形後はカーソルが1行目に移り、元の折りたたみの範囲に入る
+        app.replace_buffer("fn a() { 1 }\nfn b() { 2 }\n");
+        assert_eq!(app.pane.cursor_line, 1);
+        assert!(app.pane.folds.is_empty());
+        assert!(!app.pane.is_hidden(app.pane.cursor_line));
+    }
+
+    #[test]
+    fn add_fold_merges_inner_folds() {
+        let mut p = pane("");
+        p.add_fold(5, 6);
+        p.add_fold(2, 4);
+        p.add_fold(1, 5);
+        assert_eq!(p.folds, vec![(1, 6)]);
+        assert!(p.is_hidden(6));
+        assert!(!p.is_hidden(1));
+        assert_eq!(p.visible_line(3), 1);
+    }
+
+    #[test]
+    fn edit_at_multiple_cursors() {
+        let mut p = pane_at("foo\nfoo foo", 0, 3);
+        p.cursors = vec![Cursor { line: 1, col: 3 }, Cursor { line: 1, col: 7 }];
+        for c in "ねx".chars() {
+            p.for_each_cursor(|pane| pane.insert_char(c));
+        }
+        assert_eq!(p.buffer.to_string(), "fooねx\nfooねx fooねx");
+        assert_eq!((p.cursor_line, p.cursor_col), (0, 5));
+        assert_eq!(p.cursors, vec![Cursor { line: 1, col: 5 }, Cursor { line: 1, col: 11 }]);
+        p.for_each_cursor(EditorPane::delete_backward);
+        assert_eq!(p.buffer.to_string(), "fooね\nfooね fooね");
+        assert_eq!(p.cursors, vec![Cursor { line: 1, col: 4 }, Cursor { line: 1, col: 9 }]);
+    }
+
+    #[test]
+    fn overlapping_cursors_merge() {
+        let mut p = pane_at("abc", 0, 2);
+        p.cursors = vec![Cursor { line: 0, col: 1 }];
+        p.for_each_cursor(EditorPane::delete_backward);
+        assert_eq!(p.buffer.to_string(), "c");
+        p.for_each_cursor(|pane| pane.move_left(1));
+        assert_eq!((p.cursor_line, p.cursor_col), (0, 0));
+        assert!(p.cursors.is_empty());
+    }
+
+    #[test]
+    fn backspace_joins_crlf_lines() {
+        let mut p = pane_at("ab\r\ncd", 1, 0);
+        p.delete_backward();
+        assert_eq!(p.buffer.to_string(), "abcd");
+        assert_eq!((p.cursor_line, p.cursor_col), (0, 2));
+    }
+
+    #[test]
+    fn backspace_full_width_char() {
+        let mut p = pane_at("日本語", 0, 2);
+        p.delete_backward();
+        assert_eq!(p.buffer.to_string(), "日語");
+        assert_eq!(p.cursor_col, 1);
+    }
+
+    #[test]
+    fn delete_at_crlf_line_end() {
+        let mut p = pane_at("ab\r\ncd", 0, 2);
+        p.delete_forward();
+        assert_eq!(p.buffer.to_string(), "abcd");
+        assert_eq!((p.cursor_line, p.cursor_col), (0, 2));
+        p.move_to_line_end();
+        p.delete_forward();
+        assert_eq!(p.buffer.to_string(), "abcd");
+    }
+}
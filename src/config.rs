@@ -0,0 +1,213 @@
+//! 設定ファイルと起動をまたいで保存する状態（最近のファイル、サイドバーの幅）
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::keymap::KeyBindingConfig;
+
+#[derive(Debug, Deserialize, Default)]
+pub struct Config {
+    #[serde(default)]
+    pub(crate) extensions: HashMap<String, String>,
+    /// 長い行を折り返して表示するか
+    #[serde(default)]
+    pub(crate) wrap_lines: bool,
+    /// 縦線のガイドを表示する桁（例: 80）
+    #[serde(default)]
+    pub(crate) wrap_guide_column: Option<usize>,
+    /// Vim風のモード編集を有効にするか
+    #[serde(default)]
+    pub(crate) vim_mode: bool,
+    /// HTMLの閉じタグやMarkdownのリストの続きを自動で挿入するか（デフォルト有効）
+    #[serde(default)]
+    pub(crate) smart_insert: Option<bool>,
+    /// サイドバーの幅（画面幅に対する%）
+    #[serde(default)]
+    pub(crate) sidebar_width_percent: Option<u16>,
+    /// エディタの右端にファイル全体の縮小表示（ミニマップ）を表示するか
+    #[serde(default)]
+    pub(crate) minimap: bool,
+    /// コメントとMarkdownの本文のスペルミスに下線を引くか
+    #[serde(default)]
+    pub(crate) spell_check: bool,
+    /// スペルチェックに使う辞書（単語リストまたはhunspellの.dic、指定がなければ標準の場所を探す）
+    #[serde(default)]
+    pub(crate) spell_dictionary: Option<PathBuf>,
+    /// サイドバーにファイルサイズを表示するか
+    #[serde(default)]
+    pub(crate) show_file_sizes: bool,
+    /// マウスホイール1回で縦スクロールする行数
+    #[serde(default)]
+    pub(crate) scroll_lines: Option<usize>,
+    /// マウスホイール1回で横スクロールする桁数
+    #[serde(default)]
+    pub(crate) scroll_columns: Option<usize>,
+    /// カーソルの上下に常に見えているようにする行数（vimのscrolloff）
+    #[serde(default)]
+    pub(crate) scroll_margin: Option<usize>,
+    /// キーバインド（アクション名 → キー指定）
+    #[serde(default)]
+    pub(crate) keybindings: HashMap<String, KeyBindingConfig>,
+    /// 保存時に実行するフォーマッタ（言語名 → コマンド）
+    #[serde(default)]
+    pub(crate) format_on_save: HashMap<String, String>,
+    /// 対応するファイルへの切り替え規則（ファイル名の末尾 → 置き換える候補、例: ".c" = [".h"]）
+    #[serde(default)]
+    pub(crate) counterparts: HashMap<String, Vec<String>>,
+    /// 画面の配色
+    #[serde(default)]
+    pub(crate) colors: ColorsConfig,
+}
+
+/// 配色の設定（色名、#rrggbb、256色の番号で指定）
+#[derive(Debug, Deserialize, Default)]
+pub struct ColorsConfig {
+    /// タイトルのブランチ名
+    pub(crate) git_branch: Option<String>,
+    /// 未保存を示す `*`
+    pub(crate) unsaved_marker: Option<String>,
+    /// 選択中のタブの背景
+    pub(crate) active_tab_bg: Option<String>,
+    /// カーソルのある行の背景（指定がなければ強調しない）
+    pub(crate) current_line_bg: Option<String>,
+    /// 端末の背景（"light" / "dark"、指定がなければ端末に問い合わせる）
+    pub(crate) background: Option<String>,
+}
+
+/// サイドバーの幅のデフォルト（%）
+pub(crate) const DEFAULT_SIDEBAR_WIDTH_PERCENT: u16 = 20;
+
+/// サイドバーの幅（%）の下限と上限
+pub(crate) const SIDEBAR_WIDTH_MIN_PERCENT: u16 = 5;
+
+pub(crate) const SIDEBAR_WIDTH_MAX_PERCENT: u16 = 80;
+
+/// マウスホイール1回のスクロール量のデフォルト（行数、桁数）
+pub(crate) const DEFAULT_SCROLL_LINES: usize = 1;
+
+pub(crate) const DEFAULT_SCROLL_COLUMNS: usize = 2;
+
+/// カーソルの上下に残す行数のデフォルト
+pub(crate) const DEFAULT_SCROLL_MARGIN: usize = 3;
+
+/// 対応するファイル（ヘッダ・テスト）への切り替え規則のデフォルト
+pub(crate) const DEFAULT_COUNTERPARTS: &[(&str, &[&str])] = &[
+    (".c", &[".h"]),
+    (".cc", &[".h", ".hpp"]),
+    (".cpp", &[".h", ".hpp"]),
+    (".h", &[".c", ".cpp", ".cc"]),
+    (".hpp", &[".cpp", ".cc"]),
+    (".rs", &["_test.rs", ".rs"]),
+    ("_test.rs", &[".rs"]),
+    (".go", &["_test.go"]),
+    ("_test.go", &[".go"]),
+    (".py", &["_test.py"]),
+    ("_test.py", &[".py"]),
+    (".js", &[".test.js", ".spec.js"]),
+    (".test.js", &[".js"]),
+    (".spec.js", &[".js"]),
+    (".jsx", &[".test.jsx", ".spec.jsx"]),
+    (".test.jsx", &[".jsx"]),
+    (".spec.jsx", &[".jsx"]),
+    (".ts", &[".test.ts", ".spec.ts"]),
+    (".test.ts", &[".ts"]),
+    (".spec.ts", &[".ts"]),
+    (".tsx", &[".test.tsx", ".spec.tsx"]),
+    (".test.tsx", &[".tsx"]),
+    (".spec.tsx", &[".tsx"]),
+];
+
+impl Config {
+    pub fn load() -> Self {
+        let config_path = dirs::config_dir()
+            .map(|p| p.join("simplide").join("config.toml"));
+
+        if let Some(path) = config_path {
+            if let Ok(content) = fs::read_to_string(&path) {
+                if let Ok(config) = toml::from_str(&content) {
+                    return config;
+                }
+            }
+        }
+        Config::default()
+    }
+}
+
+/// デフォルトの切り替え規則に設定ファイルの規則を上書きし、長い末尾から順に並べる
+/// （"foo.test.ts" が ".ts" ではなく ".test.ts" に一致するように）
+pub(crate) fn counterpart_rules(overrides: &HashMap<String, Vec<String>>) -> Vec<(String, Vec<String>)> {
+    let mut rules: HashMap<String, Vec<String>> = DEFAULT_COUNTERPARTS.iter()
+        .map(|(suffix, replacements)| (suffix.to_string(), replacements.iter().map(|r| r.to_string()).collect()))
+        .collect();
+    rules.extend(overrides.iter().map(|(suffix, replacements)| (suffix.clone(), replacements.clone())));
+    let mut rules: Vec<_> = rules.into_iter().collect();
+    rules.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+    rules
+}
+
+/// 最近開いたファイルの最大件数
+pub(crate) const RECENT_FILES_MAX: usize = 50;
+
+/// 最近開いたファイルの一覧の保存先
+pub(crate) fn recent_files_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|p| p.join("simplide").join("recent_files"))
+}
+
+/// 最近開いたファイルの一覧を読み込む（新しい順）
+pub(crate) fn load_recent_files() -> Vec<PathBuf> {
+    recent_files_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|content| {
+            content.lines()
+                .filter(|line| !line.is_empty())
+                .map(PathBuf::from)
+                .take(RECENT_FILES_MAX)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 最近開いたファイルの一覧を保存（失敗しても編集は続けられるので無視）
+pub(crate) fn save_recent_files(files: &[PathBuf]) {
+    let Some(path) = recent_files_path() else {
+        return;
+    };
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let content: String = files.iter()
+        .map(|f| format!("{}\n", f.display()))
+        .collect();
+    let _ = fs::write(path, content);
+}
+
+/// スペルチェックの個人辞書（1行に1単語）
+pub(crate) fn personal_dictionary_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|p| p.join("simplide").join("dictionary"))
+}
+
+/// ドラッグで変更したサイドバーの幅の保存先
+pub(crate) fn sidebar_width_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|p| p.join("simplide").join("sidebar_width"))
+}
+
+/// 保存されたサイドバーの幅（%）を読み込む
+pub(crate) fn load_sidebar_width() -> Option<u16> {
+    sidebar_width_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| content.trim().parse().ok())
+}
+
+/// サイドバーの幅（%）を保存（失敗しても編集は続けられるので無視）
+pub(crate) fn save_sidebar_width(percent: u16) {
+    let Some(path) = sidebar_width_path() else {
+        return;
+    };
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let _ = fs::write(path, format!("{}\n", percent));
+}
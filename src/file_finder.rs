@@ -0,0 +1,220 @@
+//! ワークスペースのファイル検索（ignoreクレートで並列に列挙し、見つかった順に一覧へ追加）
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+use ignore::{WalkBuilder, WalkState};
+
+use crate::app::FilterList;
+
+/// 列挙するファイル数の上限（巨大なリポジトリでも際限なく読まない）
+const FILE_FINDER_MAX_FILES: usize = 100_000;
+
+/// 列挙を行うスレッド数の上限
+const MAX_WALKERS: usize = 8;
+
+/// ファイル検索の一覧（表示中のみAppが持ち、閉じて破棄すると列挙も止まる）
+pub(crate) struct FileFinder {
+    pub(crate) list: FilterList,
+    /// 見つかったファイルと表示用のパス
+    pub(crate) files: Vec<(PathBuf, String)>,
+    /// 入力に一致するファイルの位置（filesのインデックス）
+    pub(crate) matches: Vec<usize>,
+    /// matchesを作ったときの入力と調べ終えたファイル数
+    matched_query: String,
+    matched_count: usize,
+    /// 列挙が終わったか
+    pub(crate) done: bool,
+    rx: Receiver<PathBuf>,
+    cancel: Arc<AtomicBool>,
+    max_files: usize,
+}
+
+impl FileFinder {
+    /// rootsの下の列挙をワーカースレッドで始める
+    pub(crate) fn start(roots: Vec<PathBuf>) -> Self {
+        Self::start_with_limit(roots, FILE_FINDER_MAX_FILES)
+    }
+
+    /// 列挙するファイル数の上限を指定して始める
+    fn start_with_limit(roots: Vec<PathBuf>, max_files: usize) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let worker_cancel = Arc::clone(&cancel);
+        thread::spawn(move || walk(roots, tx, worker_cancel, max_files));
+        Self {
+            list: FilterList::default(),
+            files: Vec::new(),
+            matches: Vec::new(),
+            matched_query: String::new(),
+            matched_count: 0,
+            done: false,
+            rx,
+            cancel,
+            max_files,
+        }
+    }
+
+    /// 届いたファイルを一覧に加える（display_pathで表示用のパスを作る）
+    pub(crate) fn receive(&mut self, display_path: impl Fn(&Path) -> String) {
+        loop {
+            match self.rx.try_recv() {
+                Ok(path) => {
+                    if self.files.len() < self.max_files {
+                        let display = display_path(&path);
+                        self.files.push((path, display));
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+        self.update_matches();
+    }
+
+    /// 入力が変わっていれば全体を、増えたファイルがあればその分だけを絞り込む
+    pub(crate) fn update_matches(&mut self) {
+        if self.matched_query != self.list.query {
+            self.matched_query = self.list.query.clone();
+            self.matched_count = 0;
+            self.matches.clear();
+        }
+        let list = &self.list;
+        let start = self.matched_count;
+        self.matches.extend(
+            self.files[start..].iter()
+                .enumerate()
+                .filter(|(_, (_, display))| list.matches(display))
+                .map(|(i, _)| start + i),
+        );
+        self.matched_count = self.files.len();
+    }
+
+    /// 選択中のファイル
+    pub(crate) fn selected_file(&self) -> Option<&PathBuf> {
+        self.matches.get(self.list.selected).map(|&i| &self.files[i].0)
+    }
+}
+
+impl Drop for FileFinder {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+/// rootsの下を複数のスレッドで並列に列挙し、見つかったファイルをtxへ送る。
+/// .gitignoreなどで除外されたものと .git ディレクトリは除き、シンボリックリンクのディレクトリは辿らない
+/// （max_files件に達したか、キャンセルされたか、受信側が閉じたら止める）
+fn walk(roots: Vec<PathBuf>, tx: Sender<PathBuf>, cancel: Arc<AtomicBool>, max_files: usize) {
+    let Some((first, rest)) = roots.split_first() else {
+        return;
+    };
+    let mut builder = WalkBuilder::new(first);
+    for root in rest {
+        builder.add(root);
+    }
+    builder
+        .hidden(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .threads(thread::available_parallelism().map_or(2, |n| n.get()).min(MAX_WALKERS));
+
+    let sent = AtomicUsize::new(0);
+    builder.build_parallel().run(|| {
+        let tx = tx.clone();
+        let cancel = &cancel;
+        let sent = &sent;
+        Box::new(move |entry| {
+            if cancel.load(Ordering::Relaxed) {
+                return WalkState::Quit;
+            }
+            let Ok(entry) = entry else {
+                return WalkState::Continue;
+            };
+            let is_file = entry.file_type()
+                .is_some_and(|t| t.is_file() || (t.is_symlink() && entry.path().is_file()));
+            if !is_file {
+                return WalkState::Continue;
+            }
+            if sent.fetch_add(1, Ordering::Relaxed) >= max_files || tx.send(entry.into_path()).is_err() {
+                return WalkState::Quit;
+            }
+            WalkState::Continue
+        })
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    /// 一時ディレクトリの下にファイルを作る
+    fn temp_tree(name: &str, files: &[String]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("simplide-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for file in files {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        root
+    }
+
+    fn walk_files(root: &Path, max_files: usize, cancel: bool) -> Vec<String> {
+        let (tx, rx) = mpsc::channel();
+        walk(vec![root.to_path_buf()], tx, Arc::new(AtomicBool::new(cancel)), max_files);
+        let mut files: Vec<String> = rx.try_iter()
+            .map(|path| path.strip_prefix(root).unwrap().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn gitignore_is_honored() {
+        let files = [".git/HEAD", ".github/ci.yml", "build/gen.rs", "src/main.rs", "target/out", "notes.log"];
+        let root = temp_tree("gitignore", &files.map(String::from));
+        fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        assert_eq!(walk_files(&root, 100, false), vec![".github/ci.yml", ".gitignore", "build/gen.rs", "src/main.rs"]);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn walk_stops_at_limit_or_cancel() {
+        let files: Vec<String> = (0..50).map(|i| format!("d{}/f{}", i % 5, i)).collect();
+        let root = temp_tree("limit", &files);
+        assert_eq!(walk_files(&root, 10, false).len(), 10);
+        assert!(walk_files(&root, 100, true).is_empty());
+        // 受信側が閉じていても止まる
+        let (tx, rx) = mpsc::channel();
+        drop(rx);
+        walk(vec![root.clone()], tx, Arc::new(AtomicBool::new(false)), 100);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn finder_receives_up_to_limit_and_cancels_on_drop() {
+        let files: Vec<String> = (0..30).map(|i| format!("f{}", i)).collect();
+        let root = temp_tree("finder", &files);
+        let mut finder = FileFinder::start_with_limit(vec![root.clone()], 20);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !finder.done && Instant::now() < deadline {
+            finder.receive(|path| path.display().to_string());
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(finder.done);
+        assert_eq!(finder.files.len(), 20);
+        let cancel = Arc::clone(&finder.cancel);
+        drop(finder);
+        assert!(cancel.load(Ordering::Relaxed));
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,688 @@
+//! Tree-sitterによるシンタックスハイライトと定義へのジャンプ
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use ratatui::style::Color;
+use tree_sitter::{Parser, Query, QueryCursor, StreamingIterator};
+use tree_sitter_highlight::{Highlight, HighlightConfiguration, HighlightEvent, Highlighter};
+
+use crate::buffer::is_word_char;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    Rust,
+    JavaScript,
+    TypeScript,
+    Tsx,
+    Go,
+    Python,
+    Json,
+    Toml,
+    Yaml,
+    Markdown,
+    MarkdownInline,
+    Php,
+    Make,
+    Hcl,
+    Html,
+    Css,
+    Sql,
+}
+
+// SQL用の数値リテラルのクエリ（tree-sitter-sequelのクエリは数値の判定がLuaパターンで書かれていて
+// マッチしないため、末尾に追加して(literal)の@stringより優先させる）
+pub(crate) const SQL_NUMBER_QUERY: &str = r#"
+((literal) @number
+  (#match? @number "^[-+]?([0-9]+[.]?[0-9]*|[.][0-9]+)$"))
+"#;
+
+// Markdown用のインジェクションクエリ（tree-sitter-mdのクエリでは強調やインラインコードの記号が
+// (inline)の子として除かれ、インラインの構文を解析できないため、子も含めて解析させる）
+pub(crate) const MARKDOWN_INJECTION_QUERY: &str = r#"
+(fenced_code_block
+  (info_string
+    (language) @injection.language)
+  (code_fence_content) @injection.content)
+
+((html_block) @injection.content (#set! injection.language "html"))
+
+(document . (section . (thematic_break) (_) @injection.content (thematic_break)) (#set! injection.language "yaml"))
+
+([(minus_metadata) (plus_metadata)] @injection.content (#set! injection.language "yml"))
+
+((inline) @injection.content
+  (#set! injection.language "markdown_inline")
+  (#set! injection.include-children))
+"#;
+
+// HCL用のハイライトクエリ（tree-sitter-hclには含まれていないため）
+pub(crate) const HCL_HIGHLIGHTS_QUERY: &str = r#"
+(comment) @comment
+(identifier) @variable
+(numeric_lit) @number
+(bool_lit) @constant.builtin
+(null_lit) @constant.builtin
+(string_lit) @string
+(heredoc_template) @string
+
+(attribute (identifier) @property)
+(block (identifier) @keyword)
+(block (string_lit) @string)
+
+(function_call (identifier) @function)
+
+["=" "==" "!=" "<" ">" "<=" ">=" "+" "-" "*" "/" "%" "&&" "||" "!"] @operator
+["(" ")" "[" "]" "{" "}"] @punctuation.bracket
+["," "." ":"] @punctuation.delimiter
+
+["for" "endfor" "in" "if" "else" "endif"] @keyword
+"#;
+
+// ハイライト名とカラーのマッピング
+pub(crate) const HIGHLIGHT_NAMES: &[&str] = &[
+    "keyword",
+    "function",
+    "type",
+    "string",
+    "number",
+    "comment",
+    "variable",
+    "operator",
+    "punctuation",
+    "constant",
+    "attribute",
+    "property",
+    // Markdown用
+    "text.title",
+    "text.literal",
+    "text.uri",
+    "text.reference",
+    "text.emphasis",
+    "text.strong",
+    "punctuation.special",
+    "punctuation.delimiter",
+    "punctuation.bracket",
+    "string.escape",
+    "markup.heading",
+    "markup.link",
+    "markup.list",
+    "markup.raw",
+    // 追加の一般的なハイライト名
+    "tag",
+    "label",
+    "namespace",
+    "module",
+    "parameter",
+    "field",
+    "constant.builtin",
+    // SQL用
+    "float",
+    "boolean",
+    "conditional",
+    "storageclass",
+];
+
+pub(crate) fn highlight_color(highlight: Highlight) -> Color {
+    match HIGHLIGHT_NAMES.get(highlight.0) {
+        Some(&"keyword") => Color::Magenta,
+        Some(&"function") => Color::Blue,
+        Some(&"type") => Color::Yellow,
+        Some(&"string") => Color::Green,
+        Some(&"number") => Color::Cyan,
+        Some(&"comment") => Color::DarkGray,
+        Some(&"variable") => Color::White,
+        Some(&"operator") => Color::Red,
+        Some(&"punctuation") => Color::White,
+        Some(&"constant") => Color::Cyan,
+        Some(&"attribute") => Color::Yellow,
+        Some(&"property") => Color::Blue,
+        // Markdown用
+        Some(&"text.title") => Color::Yellow,
+        Some(&"text.literal") => Color::Green,
+        Some(&"text.uri") => Color::Cyan,
+        Some(&"text.reference") => Color::Blue,
+        Some(&"text.emphasis") => Color::LightYellow,
+        Some(&"text.strong") => Color::LightRed,
+        Some(&"punctuation.special") => Color::Magenta,
+        Some(&"punctuation.delimiter") => Color::DarkGray,
+        Some(&"punctuation.bracket") => Color::White,
+        Some(&"string.escape") => Color::Red,
+        Some(&"markup.heading") => Color::Yellow,
+        Some(&"markup.link") => Color::Cyan,
+        Some(&"markup.list") => Color::Magenta,
+        Some(&"markup.raw") => Color::Green,
+        // 追加
+        Some(&"tag") => Color::Red,
+        Some(&"label") => Color::Yellow,
+        Some(&"namespace") => Color::Yellow,
+        Some(&"module") => Color::Yellow,
+        Some(&"parameter") => Color::White,
+        Some(&"field") => Color::Blue,
+        Some(&"constant.builtin") => Color::Cyan,
+        // SQL用
+        Some(&"float") => Color::Cyan,
+        Some(&"boolean") => Color::Cyan,
+        Some(&"conditional") => Color::Magenta,
+        Some(&"storageclass") => Color::Magenta,
+        _ => Color::White,
+    }
+}
+
+// 定義へのジャンプ用のローカル変数のクエリ（各パーサーには含まれていないため）
+pub(crate) const RUST_LOCALS_QUERY: &str = r#"
+[
+  (block)
+  (function_item)
+  (closure_expression)
+  (for_expression)
+  (while_expression)
+  (if_expression)
+  (match_arm)
+] @local.scope
+
+(parameter pattern: (identifier) @local.definition)
+(let_declaration pattern: (identifier) @local.definition)
+(let_condition pattern: (identifier) @local.definition)
+(closure_parameters (identifier) @local.definition)
+(for_expression pattern: (identifier) @local.definition)
+(tuple_pattern (identifier) @local.definition)
+(tuple_struct_pattern (identifier) @local.definition)
+(match_pattern (identifier) @local.definition)
+(const_item name: (identifier) @local.definition)
+(static_item name: (identifier) @local.definition)
+"#;
+
+pub(crate) const GO_LOCALS_QUERY: &str = r#"
+[
+  (function_declaration)
+  (method_declaration)
+  (func_literal)
+  (block)
+  (if_statement)
+  (for_statement)
+  (expression_switch_statement)
+  (type_switch_statement)
+] @local.scope
+
+(parameter_declaration name: (identifier) @local.definition)
+(variadic_parameter_declaration name: (identifier) @local.definition)
+(short_var_declaration left: (expression_list (identifier) @local.definition))
+(range_clause left: (expression_list (identifier) @local.definition))
+(var_spec name: (identifier) @local.definition)
+(const_spec name: (identifier) @local.definition)
+"#;
+
+pub(crate) const PYTHON_LOCALS_QUERY: &str = r#"
+[
+  (function_definition)
+  (lambda)
+] @local.scope
+
+(parameters (identifier) @local.definition)
+(default_parameter name: (identifier) @local.definition)
+(typed_parameter (identifier) @local.definition)
+(typed_default_parameter name: (identifier) @local.definition)
+(lambda_parameters (identifier) @local.definition)
+(assignment left: (identifier) @local.definition)
+(pattern_list (identifier) @local.definition)
+(for_statement left: (identifier) @local.definition)
+(aliased_import alias: (identifier) @local.definition)
+(import_statement name: (dotted_name (identifier) @local.definition))
+(import_from_statement name: (dotted_name (identifier) @local.definition))
+"#;
+
+/// 定義へのジャンプに使うクエリ（ローカル変数のスコープ解決と、関数・型などの定義）
+pub(crate) struct DefinitionQueries {
+    pub(crate) language: tree_sitter::Language,
+    pub(crate) locals: Query,
+    pub(crate) tags: Query,
+}
+
+pub struct SyntaxHighlighter {
+    pub(crate) highlighter: Highlighter,
+    pub(crate) configs: HashMap<Language, HighlightConfiguration>,
+    pub(crate) extension_map: HashMap<String, Language>,
+    pub(crate) definitions: HashMap<Language, DefinitionQueries>,
+}
+
+impl SyntaxHighlighter {
+    pub fn new(custom_extensions: &HashMap<String, String>) -> Self {
+        let highlighter = Highlighter::new();
+        let mut configs = HashMap::new();
+
+        // Rust
+        if let Ok(mut config) = HighlightConfiguration::new(
+            tree_sitter_rust::LANGUAGE.into(),
+            "rust",
+            tree_sitter_rust::HIGHLIGHTS_QUERY,
+            "", "",
+        ) {
+            config.configure(HIGHLIGHT_NAMES);
+            configs.insert(Language::Rust, config);
+        }
+
+        // JavaScript
+        if let Ok(mut config) = HighlightConfiguration::new(
+            tree_sitter_javascript::LANGUAGE.into(),
+            "javascript",
+            tree_sitter_javascript::HIGHLIGHT_QUERY,
+            tree_sitter_javascript::INJECTIONS_QUERY,
+            tree_sitter_javascript::LOCALS_QUERY,
+        ) {
+            config.configure(HIGHLIGHT_NAMES);
+            configs.insert(Language::JavaScript, config);
+        }
+
+        // TypeScript
+        if let Ok(mut config) = HighlightConfiguration::new(
+            tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            "typescript",
+            tree_sitter_typescript::HIGHLIGHTS_QUERY,
+            "", "",
+        ) {
+            config.configure(HIGHLIGHT_NAMES);
+            configs.insert(Language::TypeScript, config);
+        }
+
+        // TSX
+        if let Ok(mut config) = HighlightConfiguration::new(
+            tree_sitter_typescript::LANGUAGE_TSX.into(),
+            "tsx",
+            tree_sitter_typescript::HIGHLIGHTS_QUERY,
+            "", "",
+        ) {
+            config.configure(HIGHLIGHT_NAMES);
+            configs.insert(Language::Tsx, config);
+        }
+
+        // Go
+        if let Ok(mut config) = HighlightConfiguration::new(
+            tree_sitter_go::LANGUAGE.into(),
+            "go",
+            tree_sitter_go::HIGHLIGHTS_QUERY,
+            "", "",
+        ) {
+            config.configure(HIGHLIGHT_NAMES);
+            configs.insert(Language::Go, config);
+        }
+
+        // Python
+        if let Ok(mut config) = HighlightConfiguration::new(
+            tree_sitter_python::LANGUAGE.into(),
+            "python",
+            tree_sitter_python::HIGHLIGHTS_QUERY,
+            "", "",
+        ) {
+            config.configure(HIGHLIGHT_NAMES);
+            configs.insert(Language::Python, config);
+        }
+
+        // JSON
+        if let Ok(mut config) = HighlightConfiguration::new(
+            tree_sitter_json::LANGUAGE.into(),
+            "json",
+            tree_sitter_json::HIGHLIGHTS_QUERY,
+            "", "",
+        ) {
+            config.configure(HIGHLIGHT_NAMES);
+            configs.insert(Language::Json, config);
+        }
+
+        // TOML
+        if let Ok(mut config) = HighlightConfiguration::new(
+            tree_sitter_toml_ng::language(),
+            "toml",
+            tree_sitter_toml_ng::HIGHLIGHTS_QUERY,
+            "", "",
+        ) {
+            config.configure(HIGHLIGHT_NAMES);
+            configs.insert(Language::Toml, config);
+        }
+
+        // YAML
+        if let Ok(mut config) = HighlightConfiguration::new(
+            tree_sitter_yaml::LANGUAGE.into(),
+            "yaml",
+            tree_sitter_yaml::HIGHLIGHTS_QUERY,
+            "", "",
+        ) {
+            config.configure(HIGHLIGHT_NAMES);
+            configs.insert(Language::Yaml, config);
+        }
+
+        // Markdown block parser
+        if let Ok(mut config) = HighlightConfiguration::new(
+            tree_sitter_md::LANGUAGE.into(),
+            "markdown",
+            tree_sitter_md::HIGHLIGHT_QUERY_BLOCK,
+            MARKDOWN_INJECTION_QUERY,
+            "",
+        ) {
+            config.configure(HIGHLIGHT_NAMES);
+            configs.insert(Language::Markdown, config);
+        }
+
+        // Markdown inline parser (for injection callback)
+        if let Ok(mut config) = HighlightConfiguration::new(
+            tree_sitter_md::INLINE_LANGUAGE.into(),
+            "markdown_inline",
+            tree_sitter_md::HIGHLIGHT_QUERY_INLINE,
+            "",
+            "",
+        ) {
+            config.configure(HIGHLIGHT_NAMES);
+            configs.insert(Language::MarkdownInline, config);
+        }
+
+        // PHP
+        if let Ok(mut config) = HighlightConfiguration::new(
+            tree_sitter_php::LANGUAGE_PHP.into(),
+            "php",
+            tree_sitter_php::HIGHLIGHTS_QUERY,
+            tree_sitter_php::INJECTIONS_QUERY,
+            "",
+        ) {
+            config.configure(HIGHLIGHT_NAMES);
+            configs.insert(Language::Php, config);
+        }
+
+        // Makefile
+        if let Ok(mut config) = HighlightConfiguration::new(
+            tree_sitter_make::LANGUAGE.into(),
+            "make",
+            tree_sitter_make::HIGHLIGHTS_QUERY,
+            "",
+            "",
+        ) {
+            config.configure(HIGHLIGHT_NAMES);
+            configs.insert(Language::Make, config);
+        }
+
+        // HCL (Terraform)
+        if let Ok(mut config) = HighlightConfiguration::new(
+            tree_sitter_hcl::LANGUAGE.into(),
+            "hcl",
+            HCL_HIGHLIGHTS_QUERY,
+            "",
+            "",
+        ) {
+            config.configure(HIGHLIGHT_NAMES);
+            configs.insert(Language::Hcl, config);
+        }
+
+        // HTML（<script>/<style>内はinjectionでJavaScript/CSSとしてハイライト）
+        if let Ok(mut config) = HighlightConfiguration::new(
+            tree_sitter_html::LANGUAGE.into(),
+            "html",
+            tree_sitter_html::HIGHLIGHTS_QUERY,
+            tree_sitter_html::INJECTIONS_QUERY,
+            "",
+        ) {
+            config.configure(HIGHLIGHT_NAMES);
+            configs.insert(Language::Html, config);
+        }
+
+        // CSS
+        if let Ok(mut config) = HighlightConfiguration::new(
+            tree_sitter_css::LANGUAGE.into(),
+            "css",
+            tree_sitter_css::HIGHLIGHTS_QUERY,
+            "",
+            "",
+        ) {
+            config.configure(HIGHLIGHT_NAMES);
+            configs.insert(Language::Css, config);
+        }
+
+        // SQL
+        let sql_query = format!("{}{}", tree_sitter_sql::HIGHLIGHTS_QUERY, SQL_NUMBER_QUERY);
+        if let Ok(mut config) = HighlightConfiguration::new(
+            tree_sitter_sql::LANGUAGE.into(),
+            "sql",
+            &sql_query,
+            "",
+            "",
+        ) {
+            config.configure(HIGHLIGHT_NAMES);
+            configs.insert(Language::Sql, config);
+        }
+
+        // デフォルトの拡張子マッピング
+        let mut extension_map = HashMap::new();
+        extension_map.insert("rs".to_string(), Language::Rust);
+        extension_map.insert("js".to_string(), Language::JavaScript);
+        extension_map.insert("mjs".to_string(), Language::JavaScript);
+        extension_map.insert("cjs".to_string(), Language::JavaScript);
+        extension_map.insert("jsx".to_string(), Language::JavaScript);
+        extension_map.insert("ts".to_string(), Language::TypeScript);
+        extension_map.insert("mts".to_string(), Language::TypeScript);
+        extension_map.insert("cts".to_string(), Language::TypeScript);
+        extension_map.insert("tsx".to_string(), Language::Tsx);
+        extension_map.insert("go".to_string(), Language::Go);
+        extension_map.insert("py".to_string(), Language::Python);
+        extension_map.insert("pyw".to_string(), Language::Python);
+        extension_map.insert("json".to_string(), Language::Json);
+        extension_map.insert("toml".to_string(), Language::Toml);
+        extension_map.insert("yaml".to_string(), Language::Yaml);
+        extension_map.insert("yml".to_string(), Language::Yaml);
+        extension_map.insert("md".to_string(), Language::Markdown);
+        extension_map.insert("markdown".to_string(), Language::Markdown);
+        extension_map.insert("php".to_string(), Language::Php);
+        extension_map.insert("mk".to_string(), Language::Make);
+        extension_map.insert("tf".to_string(), Language::Hcl);
+        extension_map.insert("tfvars".to_string(), Language::Hcl);
+        extension_map.insert("hcl".to_string(), Language::Hcl);
+        extension_map.insert("html".to_string(), Language::Html);
+        extension_map.insert("htm".to_string(), Language::Html);
+        extension_map.insert("css".to_string(), Language::Css);
+        extension_map.insert("scss".to_string(), Language::Css);
+        extension_map.insert("sql".to_string(), Language::Sql);
+
+        // カスタム拡張子マッピングを適用
+        for (ext, lang_str) in custom_extensions {
+            if let Some(lang) = Self::parse_language(lang_str) {
+                extension_map.insert(ext.clone(), lang);
+            }
+        }
+
+        // 定義へのジャンプに対応する言語
+        let definition_sources: [(Language, tree_sitter::Language, &str, &str); 4] = [
+            (Language::Rust, tree_sitter_rust::LANGUAGE.into(), RUST_LOCALS_QUERY, tree_sitter_rust::TAGS_QUERY),
+            (Language::Go, tree_sitter_go::LANGUAGE.into(), GO_LOCALS_QUERY, tree_sitter_go::TAGS_QUERY),
+            (Language::Python, tree_sitter_python::LANGUAGE.into(), PYTHON_LOCALS_QUERY, tree_sitter_python::TAGS_QUERY),
+            (
+                Language::JavaScript,
+                tree_sitter_javascript::LANGUAGE.into(),
+                tree_sitter_javascript::LOCALS_QUERY,
+                tree_sitter_javascript::TAGS_QUERY,
+            ),
+        ];
+        let mut definitions = HashMap::new();
+        for (lang, language, locals, tags) in definition_sources {
+            if let (Ok(locals), Ok(tags)) = (Query::new(&language, locals), Query::new(&language, tags)) {
+                definitions.insert(lang, DefinitionQueries { language, locals, tags });
+            }
+        }
+
+        SyntaxHighlighter {
+            highlighter,
+            configs,
+            extension_map,
+            definitions,
+        }
+    }
+
+    /// カーソル位置（バイト）の識別子の定義の位置（バイト）を同じファイル内から探す
+    pub fn find_definition(&self, source: &str, language: Language, offset: usize) -> Option<usize> {
+        let queries = self.definitions.get(&language)?;
+        let mut parser = Parser::new();
+        parser.set_language(&queries.language).ok()?;
+        let tree = parser.parse(source, None)?;
+        let root = tree.root_node();
+        let bytes = source.as_bytes();
+
+        // 識別子の直後にカーソルがある場合も対象にする
+        let node = [offset, offset.saturating_sub(1)].into_iter()
+            .filter_map(|b| root.descendant_for_byte_range(b, b))
+            .find(|n| {
+                n.child_count() == 0
+                    && n.utf8_text(bytes).is_ok_and(|text| {
+                        text.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+                            && text.chars().all(is_word_char)
+                    })
+            })?;
+        let name = node.utf8_text(bytes).ok()?;
+        let reference = node.start_byte();
+
+        // ローカル変数は参照を含む最も内側のスコープの定義（参照より前で最も近いものを優先）
+        let mut scopes = vec![root.byte_range()];
+        let mut candidates = Vec::new();
+        let capture_names = queries.locals.capture_names();
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(&queries.locals, root, bytes);
+        while let Some(m) = matches.next() {
+            for capture in m.captures {
+                match capture_names[capture.index as usize] {
+                    "local.scope" => scopes.push(capture.node.byte_range()),
+                    "local.definition" if capture.node.utf8_text(bytes) == Ok(name) => {
+                        candidates.push(capture.node.start_byte());
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let local = candidates.into_iter()
+            .filter_map(|def| {
+                let scope = scopes.iter().filter(|s| s.contains(&def)).min_by_key(|s| s.len())?;
+                scope.contains(&reference).then_some((scope.len(), def))
+            })
+            .min_by_key(|&(scope_len, def)| {
+                let distance = if def <= reference { reference - def } else { def };
+                (scope_len, def > reference, distance)
+            });
+        if let Some((_, def)) = local {
+            return Some(def);
+        }
+
+        // 関数・型などはファイル内の定義から名前で探す
+        let name_index = queries.tags.capture_index_for_name("name")?;
+        let capture_names = queries.tags.capture_names();
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(&queries.tags, root, bytes);
+        while let Some(m) = matches.next() {
+            let is_definition = m.captures.iter()
+                .any(|c| capture_names[c.index as usize].starts_with("definition."));
+            if !is_definition {
+                continue;
+            }
+            if let Some(c) = m.captures.iter().find(|c| c.index == name_index && c.node.utf8_text(bytes) == Ok(name)) {
+                return Some(c.node.start_byte());
+            }
+        }
+        None
+    }
+
+    pub fn parse_language(s: &str) -> Option<Language> {
+        match s.to_lowercase().as_str() {
+            "rust" | "rs" => Some(Language::Rust),
+            "javascript" | "js" => Some(Language::JavaScript),
+            "typescript" | "ts" => Some(Language::TypeScript),
+            "tsx" => Some(Language::Tsx),
+            "go" | "golang" => Some(Language::Go),
+            "python" | "py" => Some(Language::Python),
+            "json" => Some(Language::Json),
+            "toml" => Some(Language::Toml),
+            "yaml" | "yml" => Some(Language::Yaml),
+            "markdown" | "md" => Some(Language::Markdown),
+            "php" => Some(Language::Php),
+            "make" | "makefile" => Some(Language::Make),
+            "hcl" | "terraform" | "tf" => Some(Language::Hcl),
+            "html" | "htm" => Some(Language::Html),
+            "css" | "scss" => Some(Language::Css),
+            "sql" => Some(Language::Sql),
+            _ => None,
+        }
+    }
+
+    pub fn detect_language(&self, path: &Path) -> Option<Language> {
+        // まずファイル名で判定（Makefileなど拡張子がないファイル用）
+        if let Some("Makefile" | "makefile" | "GNUmakefile") = path.file_name().and_then(|n| n.to_str()) {
+            return Some(Language::Make);
+        }
+        // 拡張子で判定
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| self.extension_map.get(ext).copied())
+    }
+
+    /// ファイル全体をハイライトして、各バイト位置に対応する色と最も内側のキャプチャ（なければNone）を返す
+    pub fn highlight_all(&mut self, source: &str, language: Language) -> (Vec<Color>, Vec<Option<Highlight>>) {
+        let unhighlighted = || (vec![Color::White; source.len()], vec![None; source.len()]);
+        let config = match self.configs.get(&language) {
+            Some(c) => c,
+            None => return unhighlighted(),
+        };
+
+        // configsへの参照を取得（borrow checkerのためにここで分離）
+        let configs = &self.configs;
+
+        // injection callback - 言語名から設定を解決（エイリアス対応）
+        let injection_callback = |lang_name: &str| -> Option<&HighlightConfiguration> {
+            let lang = match lang_name {
+                "rust" | "rs" => Some(Language::Rust),
+                "javascript" | "js" | "jsx" => Some(Language::JavaScript),
+                "typescript" | "ts" => Some(Language::TypeScript),
+                "tsx" => Some(Language::Tsx),
+                "go" | "golang" => Some(Language::Go),
+                "python" | "py" | "python3" => Some(Language::Python),
+                "json" | "jsonc" => Some(Language::Json),
+                "toml" => Some(Language::Toml),
+                "yaml" | "yml" => Some(Language::Yaml),
+                "markdown" | "md" => Some(Language::Markdown),
+                "markdown_inline" => Some(Language::MarkdownInline),
+                "php" => Some(Language::Php),
+                "make" | "makefile" | "Makefile" => Some(Language::Make),
+                "hcl" | "terraform" | "tf" => Some(Language::Hcl),
+                "html" | "htm" => Some(Language::Html),
+                "css" | "scss" => Some(Language::Css),
+                "sql" => Some(Language::Sql),
+                _ => None,
+            };
+            lang.and_then(|l| configs.get(&l))
+        };
+
+        let highlights = match self.highlighter.highlight(config, source.as_bytes(), None, injection_callback) {
+            Ok(h) => h,
+            Err(_) => return unhighlighted(),
+        };
+
+        let (mut colors, mut captures) = unhighlighted();
+        let mut current_color = Color::White;
+        let mut color_stack: Vec<Color> = Vec::new();
+        let mut capture_stack: Vec<Highlight> = Vec::new();
+
+        for event in highlights {
+            match event {
+                Ok(HighlightEvent::Source { start, end }) => {
+                    let end = end.min(colors.len());
+                    let start = start.min(end);
+                    colors[start..end].fill(current_color);
+                    captures[start..end].fill(capture_stack.last().copied());
+                }
+                Ok(HighlightEvent::HighlightStart(h)) => {
+                    color_stack.push(current_color);
+                    capture_stack.push(h);
+                    current_color = highlight_color(h);
+                }
+                Ok(HighlightEvent::HighlightEnd) => {
+                    current_color = color_stack.pop().unwrap_or(Color::White);
+                    capture_stack.pop();
+                }
+                Err(_) => break,
+            }
+        }
+
+        (colors, captures)
+    }
+}
@@ -0,0 +1,223 @@
+//! 画像の読み込みと拡大・移動
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::layout::Rect;
+use ratatui_image::{
+    picker::Picker,
+    protocol::StatefulProtocol,
+    thread::ThreadProtocol,
+    Resize,
+};
+
+use crate::app::App;
+
+/// 画像として表示するラスタ形式の拡張子（imageクレートでデコード）
+pub(crate) const RASTER_IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "bmp", "tiff", "tif", "ico"];
+
+/// 画像ファイルの種類（デコード方法の判定用）
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum ImageKind {
+    Raster,
+    #[cfg(feature = "svg")]
+    Svg,
+}
+
+/// 拡張子から画像の種類を判定（is_image_fileとdecode_imageで共有）
+pub(crate) fn image_kind(path: &Path) -> Option<ImageKind> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    if RASTER_IMAGE_EXTENSIONS.contains(&ext.as_str()) {
+        return Some(ImageKind::Raster);
+    }
+    #[cfg(feature = "svg")]
+    if ext == "svg" {
+        return Some(ImageKind::Svg);
+    }
+    None
+}
+
+pub(crate) fn is_image_file(path: &Path) -> bool {
+    image_kind(path).is_some()
+}
+
+pub(crate) fn decode_image(path: &Path) -> Option<image::DynamicImage> {
+    match image_kind(path)? {
+        ImageKind::Raster => image::ImageReader::open(path)
+            .ok()?
+            .decode()
+            .ok(),
+        #[cfg(feature = "svg")]
+        ImageKind::Svg => decode_svg(path),
+    }
+}
+
+/// SVGをラスタライズしてDynamicImageに変換
+#[cfg(feature = "svg")]
+pub(crate) fn decode_svg(path: &Path) -> Option<image::DynamicImage> {
+    use resvg::{tiny_skia, usvg};
+
+    let data = fs::read(path).ok()?;
+    let tree = usvg::Tree::from_data(&data, &usvg::Options::default()).ok()?;
+    let size = tree.size().to_int_size();
+    let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height())?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+
+    // tiny-skiaは乗算済みアルファなので通常のRGBAに戻す
+    let rgba: Vec<u8> = pixmap.pixels()
+        .iter()
+        .flat_map(|p| {
+            let c = p.demultiply();
+            [c.red(), c.green(), c.blue(), c.alpha()]
+        })
+        .collect();
+    image::RgbaImage::from_raw(size.width(), size.height(), rgba)
+        .map(image::DynamicImage::ImageRgba8)
+}
+
+/// 画像リサイズ要求（ワーカースレッドへ送信）
+pub(crate) type ResizeRequest = (StatefulProtocol, Resize, Rect);
+
+/// 画像デコード要求（ワーカースレッドへ送信）。先頭は要求の世代番号
+pub(crate) type DecodeRequest = (u64, PathBuf, ImageView, Picker, Sender<ResizeRequest>);
+
+/// 画像デコード結果（ワーカースレッドから受信）。サイズは元画像のピクセル数
+pub(crate) type DecodeResult = (u64, ThreadProtocol, (u32, u32));
+
+/// 画像のデコード中に表示するスピナー
+pub(crate) const SPINNER_FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// スピナーの1コマの表示時間
+pub(crate) const SPINNER_FRAME_DURATION: Duration = Duration::from_millis(80);
+
+/// 画像の拡大率の上限
+pub(crate) const IMAGE_ZOOM_MAX: f32 = 16.0;
+
+/// 1回の拡大・縮小の倍率
+pub(crate) const IMAGE_ZOOM_STEP: f32 = 1.25;
+
+/// 画像の表示領域が変わってからリサイズを要求するまでの待ち時間（ウィンドウのドラッグ中に再エンコードしないため）
+pub(crate) const IMAGE_RESIZE_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// 画像の表示範囲（拡大率と表示中心）
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct ImageView {
+    /// 拡大率（1.0で全体表示）
+    pub(crate) zoom: f32,
+    /// 表示中心（画像サイズに対する割合 0.0〜1.0）
+    pub(crate) center: (f32, f32),
+}
+
+impl Default for ImageView {
+    fn default() -> Self {
+        Self { zoom: 1.0, center: (0.5, 0.5) }
+    }
+}
+
+impl ImageView {
+    pub(crate) fn is_zoomed(&self) -> bool {
+        self.zoom > 1.0
+    }
+
+    /// 表示範囲が画像からはみ出さないように中心を制限
+    pub(crate) fn clamp_center(&mut self) {
+        let half = 0.5 / self.zoom;
+        self.center.0 = self.center.0.clamp(half, 1.0 - half);
+        self.center.1 = self.center.1.clamp(half, 1.0 - half);
+    }
+
+    /// 表示範囲で画像を切り抜く
+    pub(crate) fn crop(&self, img: &image::DynamicImage) -> image::DynamicImage {
+        if !self.is_zoomed() {
+            return img.clone();
+        }
+        let width = ((img.width() as f32 / self.zoom) as u32).max(1);
+        let height = ((img.height() as f32 / self.zoom) as u32).max(1);
+        let x = (self.center.0 * img.width() as f32 - width as f32 / 2.0).max(0.0) as u32;
+        let y = (self.center.1 * img.height() as f32 - height as f32 / 2.0).max(0.0) as u32;
+        img.crop_imm(
+            x.min(img.width() - width),
+            y.min(img.height() - height),
+            width,
+            height,
+        )
+    }
+}
+
+impl App {
+    /// 画像の表示範囲を変更してデコードスレッドに再要求
+    pub(crate) fn set_image_view(&mut self, mut view: ImageView) {
+        view.zoom = view.zoom.clamp(1.0, IMAGE_ZOOM_MAX);
+        view.clamp_center();
+        if view == self.image_view {
+            return;
+        }
+        self.image_view = view;
+        if let Some(path) = self.pane.file_path.clone() {
+            // 現在の画像は新しい表示範囲が届くまで表示したままにする
+            self.request_image_decode(path);
+        }
+    }
+
+    /// デコードスレッドに画像を要求（以前の要求の結果は受け取らない）
+    pub(crate) fn request_image_decode(&mut self, path: PathBuf) {
+        self.image_generation += 1;
+        let _ = self.decode_tx.send((self.image_generation, path, self.image_view, self.picker, self.image_tx.clone()));
+    }
+
+    /// 画像のデコード結果を受け取る（古い要求の結果や画像以外を表示中なら捨てる）
+    pub(crate) fn receive_decoded_image(&mut self) {
+        if let Ok((generation, thread_protocol, dimensions)) = self.decode_rx.try_recv() {
+            if generation != self.image_generation || !self.pane.is_image_mode {
+                return;
+            }
+            self.image_state = Some(thread_protocol);
+            self.image_dimensions = Some(dimensions);
+            self.image_loading = None;
+        }
+    }
+
+    /// デコード中の画像の読み込みを中止（結果が届いても表示しない）
+    pub(crate) fn cancel_image_decode(&mut self) {
+        self.image_generation += 1;
+        self.image_loading = None;
+    }
+
+    /// 画像を拡大・縮小（factor > 1.0 で拡大）
+    pub(crate) fn zoom_image(&mut self, factor: f32) {
+        let view = ImageView { zoom: self.image_view.zoom * factor, ..self.image_view };
+        self.set_image_view(view);
+    }
+
+    /// 拡大中の画像を移動（dx, dyは表示範囲に対する割合）
+    pub(crate) fn pan_image(&mut self, dx: f32, dy: f32) {
+        let view = self.image_view;
+        let center = (
+            view.center.0 + dx / view.zoom,
+            view.center.1 + dy / view.zoom,
+        );
+        self.set_image_view(ImageView { center, ..view });
+    }
+
+    /// 画像モードのキー操作（処理した場合はtrue）
+    pub(crate) fn handle_image_key(&mut self, key: &KeyEvent) -> bool {
+        if !key.modifiers.difference(KeyModifiers::SHIFT).is_empty() {
+            return false;
+        }
+        match key.code {
+            KeyCode::Esc if self.image_loading.is_some() => self.cancel_image_decode(),
+            KeyCode::Char('+') | KeyCode::Char('=') => self.zoom_image(IMAGE_ZOOM_STEP),
+            KeyCode::Char('-') => self.zoom_image(1.0 / IMAGE_ZOOM_STEP),
+            KeyCode::Char('0') => self.set_image_view(ImageView::default()),
+            KeyCode::Left => self.pan_image(-0.1, 0.0),
+            KeyCode::Right => self.pan_image(0.1, 0.0),
+            KeyCode::Up => self.pan_image(0.0, -0.1),
+            KeyCode::Down => self.pan_image(0.0, 0.1),
+            _ => return false,
+        }
+        true
+    }
+}
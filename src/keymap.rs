@@ -0,0 +1,575 @@
+//! アクションとキーバインド
+
+use std::collections::HashMap;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+
+/// キー操作で実行するアクション（設定ファイルの[keybindings]でアクション名を指定）
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(crate) enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    LineStart,
+    LineEnd,
+    DeleteBackward,
+    DeleteForward,
+    Newline,
+    KillLine,
+    TransposeChars,
+    SetMark,
+    Cancel,
+    KillRegion,
+    CopyRegion,
+    Yank,
+    YankPop,
+    UniversalArgument,
+    Save,
+    SaveAs,
+    SaveAll,
+    Copy,
+    Quit,
+    CloseTab,
+    Search,
+    GoToLine,
+    CommandPalette,
+    RecentFiles,
+    FindFile,
+    Complete,
+    FormatDocument,
+    NextTab,
+    PrevTab,
+    ToggleWrap,
+    ToggleSmartInsert,
+    ToggleAnsiColors,
+    ToggleSidebar,
+    FocusSidebar,
+    AddFolder,
+    RemoveFolder,
+    ToggleTerminal,
+    SplitBelow,
+    SplitRight,
+    OtherPane,
+    ClosePane,
+    CloseOtherPanes,
+    ShowDiff,
+    ToggleBookmark,
+    NextBookmark,
+    PrevBookmark,
+    JumpBack,
+    JumpForward,
+    GoToDefinition,
+    ToggleCounterpart,
+    FoldBlock,
+    UnfoldBlock,
+    AddCursorAtNextMatch,
+    SpellSuggest,
+    ScrollUp,
+    ScrollDown,
+    ScrollLeft,
+    ScrollRight,
+}
+
+impl Action {
+    pub(crate) const ALL: &'static [Action] = &[
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::LineStart,
+        Action::LineEnd,
+        Action::DeleteBackward,
+        Action::DeleteForward,
+        Action::Newline,
+        Action::KillLine,
+        Action::TransposeChars,
+        Action::SetMark,
+        Action::Cancel,
+        Action::KillRegion,
+        Action::CopyRegion,
+        Action::Yank,
+        Action::YankPop,
+        Action::UniversalArgument,
+        Action::Save,
+        Action::SaveAs,
+        Action::SaveAll,
+        Action::Copy,
+        Action::Quit,
+        Action::CloseTab,
+        Action::Search,
+        Action::GoToLine,
+        Action::CommandPalette,
+        Action::RecentFiles,
+        Action::FindFile,
+        Action::Complete,
+        Action::FormatDocument,
+        Action::NextTab,
+        Action::PrevTab,
+        Action::ToggleWrap,
+        Action::ToggleSmartInsert,
+        Action::ToggleAnsiColors,
+        Action::ToggleSidebar,
+        Action::FocusSidebar,
+        Action::AddFolder,
+        Action::RemoveFolder,
+        Action::ToggleTerminal,
+        Action::SplitBelow,
+        Action::SplitRight,
+        Action::OtherPane,
+        Action::ClosePane,
+        Action::CloseOtherPanes,
+        Action::ShowDiff,
+        Action::ToggleBookmark,
+        Action::NextBookmark,
+        Action::PrevBookmark,
+        Action::JumpBack,
+        Action::JumpForward,
+        Action::GoToDefinition,
+        Action::ToggleCounterpart,
+        Action::FoldBlock,
+        Action::UnfoldBlock,
+        Action::AddCursorAtNextMatch,
+        Action::SpellSuggest,
+        Action::ScrollUp,
+        Action::ScrollDown,
+        Action::ScrollLeft,
+        Action::ScrollRight,
+    ];
+
+    /// 設定ファイルで使うアクション名
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::LineStart => "line_start",
+            Action::LineEnd => "line_end",
+            Action::DeleteBackward => "delete_backward",
+            Action::DeleteForward => "delete_forward",
+            Action::Newline => "newline",
+            Action::KillLine => "kill_line",
+            Action::TransposeChars => "transpose_chars",
+            Action::SetMark => "set_mark",
+            Action::Cancel => "cancel",
+            Action::KillRegion => "kill_region",
+            Action::CopyRegion => "copy_region",
+            Action::Yank => "yank",
+            Action::YankPop => "yank_pop",
+            Action::UniversalArgument => "universal_argument",
+            Action::Save => "save",
+            Action::SaveAs => "save_as",
+            Action::SaveAll => "save_all",
+            Action::Copy => "copy",
+            Action::Quit => "quit",
+            Action::CloseTab => "close_tab",
+            Action::Search => "search",
+            Action::GoToLine => "go_to_line",
+            Action::CommandPalette => "command_palette",
+            Action::RecentFiles => "recent_files",
+            Action::FindFile => "find_file",
+            Action::Complete => "complete",
+            Action::FormatDocument => "format_document",
+            Action::NextTab => "next_tab",
+            Action::PrevTab => "prev_tab",
+            Action::ToggleWrap => "toggle_wrap",
+            Action::ToggleSmartInsert => "toggle_smart_insert",
+            Action::ToggleAnsiColors => "toggle_ansi_colors",
+            Action::ToggleSidebar => "toggle_sidebar",
+            Action::FocusSidebar => "focus_sidebar",
+            Action::AddFolder => "add_folder",
+            Action::RemoveFolder => "remove_folder",
+            Action::ToggleTerminal => "toggle_terminal",
+            Action::SplitBelow => "split_below",
+            Action::SplitRight => "split_right",
+            Action::OtherPane => "other_pane",
+            Action::ClosePane => "close_pane",
+            Action::CloseOtherPanes => "close_other_panes",
+            Action::ShowDiff => "show_diff",
+            Action::ToggleBookmark => "toggle_bookmark",
+            Action::NextBookmark => "next_bookmark",
+            Action::PrevBookmark => "prev_bookmark",
+            Action::JumpBack => "jump_back",
+            Action::JumpForward => "jump_forward",
+            Action::GoToDefinition => "go_to_definition",
+            Action::ToggleCounterpart => "toggle_counterpart",
+            Action::FoldBlock => "fold_block",
+            Action::UnfoldBlock => "unfold_block",
+            Action::AddCursorAtNextMatch => "add_cursor_at_next_match",
+            Action::SpellSuggest => "spell_suggest",
+            Action::ScrollUp => "scroll_up",
+            Action::ScrollDown => "scroll_down",
+            Action::ScrollLeft => "scroll_left",
+            Action::ScrollRight => "scroll_right",
+        }
+    }
+
+    /// コマンドパレットに表示する名前
+    pub(crate) fn title(self) -> &'static str {
+        match self {
+            Action::MoveUp => "Move Up",
+            Action::MoveDown => "Move Down",
+            Action::MoveLeft => "Move Left",
+            Action::MoveRight => "Move Right",
+            Action::LineStart => "Go To Line Start",
+            Action::LineEnd => "Go To Line End",
+            Action::DeleteBackward => "Delete Backward",
+            Action::DeleteForward => "Delete Forward",
+            Action::Newline => "Insert Newline",
+            Action::KillLine => "Kill Line",
+            Action::TransposeChars => "Transpose Chars",
+            Action::SetMark => "Set Mark",
+            Action::Cancel => "Cancel Selection",
+            Action::KillRegion => "Kill Region",
+            Action::CopyRegion => "Copy Region",
+            Action::Yank => "Yank",
+            Action::YankPop => "Yank Pop",
+            Action::UniversalArgument => "Universal Argument",
+            Action::Save => "Save",
+            Action::SaveAs => "Save As",
+            Action::SaveAll => "Save All",
+            Action::Copy => "Copy",
+            Action::Quit => "Quit",
+            Action::CloseTab => "Close Tab",
+            Action::Search => "Search",
+            Action::GoToLine => "Go To Line",
+            Action::CommandPalette => "Command Palette",
+            Action::RecentFiles => "Recent Files",
+            Action::FindFile => "Find File in Workspace",
+            Action::Complete => "Complete Word",
+            Action::FormatDocument => "Format Document",
+            Action::NextTab => "Next Tab",
+            Action::PrevTab => "Previous Tab",
+            Action::ToggleWrap => "Toggle Line Wrap",
+            Action::ToggleSmartInsert => "Toggle Smart Insert (HTML Tags / Markdown Lists)",
+            Action::ToggleAnsiColors => "Toggle ANSI Colors (Log View)",
+            Action::ToggleSidebar => "Toggle Sidebar",
+            Action::FocusSidebar => "Focus Sidebar",
+            Action::AddFolder => "Add Folder to Workspace",
+            Action::RemoveFolder => "Remove Folder from Workspace",
+            Action::ToggleTerminal => "Toggle Terminal",
+            Action::SplitBelow => "Split Pane Below",
+            Action::SplitRight => "Split Pane Right",
+            Action::OtherPane => "Switch Pane",
+            Action::ClosePane => "Close Pane",
+            Action::CloseOtherPanes => "Close Other Panes",
+            Action::ShowDiff => "Show Diff Against Saved",
+            Action::ToggleBookmark => "Toggle Bookmark",
+            Action::NextBookmark => "Next Bookmark",
+            Action::PrevBookmark => "Previous Bookmark",
+            Action::JumpBack => "Jump Back",
+            Action::JumpForward => "Jump Forward",
+            Action::GoToDefinition => "Go to Definition",
+            Action::ToggleCounterpart => "Toggle Counterpart File",
+            Action::FoldBlock => "Fold Block",
+            Action::UnfoldBlock => "Unfold Block",
+            Action::AddCursorAtNextMatch => "Add Cursor at Next Occurrence",
+            Action::SpellSuggest => "Spelling Suggestions",
+            Action::ScrollUp => "Scroll Up",
+            Action::ScrollDown => "Scroll Down",
+            Action::ScrollLeft => "Scroll Left",
+            Action::ScrollRight => "Scroll Right",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|action| action.name() == name)
+    }
+}
+
+/// デフォルトのキーバインド（設定ファイルで指定されたアクションは置き換え、空白区切りで複数キーの連続入力）
+pub(crate) const DEFAULT_KEYBINDINGS: &[(Action, &str)] = &[
+    (Action::MoveUp, "up"),
+    (Action::MoveUp, "ctrl-p"),
+    (Action::MoveDown, "down"),
+    (Action::MoveDown, "ctrl-n"),
+    (Action::MoveLeft, "left"),
+    (Action::MoveLeft, "ctrl-b"),
+    (Action::MoveRight, "right"),
+    (Action::LineStart, "ctrl-a"),
+    (Action::LineEnd, "ctrl-e"),
+    (Action::DeleteBackward, "backspace"),
+    (Action::DeleteBackward, "ctrl-h"),
+    (Action::DeleteForward, "delete"),
+    (Action::DeleteForward, "ctrl-d"),
+    (Action::Newline, "enter"),
+    (Action::KillLine, "ctrl-k"),
+    (Action::TransposeChars, "ctrl-t"),
+    (Action::SetMark, "ctrl-space"),
+    (Action::SetMark, "ctrl-@"),
+    (Action::Cancel, "esc"),
+    (Action::Cancel, "ctrl-g"),
+    (Action::KillRegion, "ctrl-w"),
+    (Action::CopyRegion, "alt-w"),
+    (Action::Yank, "ctrl-y"),
+    (Action::YankPop, "alt-y"),
+    (Action::UniversalArgument, "ctrl-u"),
+    (Action::Save, "ctrl-s"),
+    (Action::Save, "super-s"),
+    (Action::SaveAs, "ctrl-x ctrl-w"),
+    (Action::SaveAll, "ctrl-x s"),
+    (Action::Copy, "super-c"),
+    (Action::Quit, "ctrl-c"),
+    (Action::Search, "ctrl-f"),
+    (Action::GoToLine, "alt-g g"),
+    (Action::GoToLine, "alt-g alt-g"),
+    (Action::CommandPalette, "ctrl-x ctrl-p"),
+    (Action::RecentFiles, "ctrl-r"),
+    (Action::FindFile, "ctrl-x f"),
+    (Action::Complete, "tab"),
+    (Action::Complete, "alt-/"),
+    (Action::FormatDocument, "alt-shift-f"),
+    (Action::NextTab, "ctrl-]"),
+    (Action::PrevTab, "ctrl-["),
+    (Action::ToggleWrap, "alt-z"),
+    (Action::ToggleSidebar, "ctrl-x ctrl-b"),
+    (Action::FocusSidebar, "ctrl-x ctrl-f"),
+    (Action::ToggleTerminal, "ctrl-x t"),
+    (Action::SplitBelow, "ctrl-x 2"),
+    (Action::SplitRight, "ctrl-x 3"),
+    (Action::OtherPane, "ctrl-x o"),
+    (Action::ClosePane, "ctrl-x 0"),
+    (Action::CloseOtherPanes, "ctrl-x 1"),
+    (Action::ShowDiff, "ctrl-x d"),
+    (Action::ToggleBookmark, "ctrl-f2"),
+    (Action::ToggleBookmark, "ctrl-x r m"),
+    (Action::NextBookmark, "f2"),
+    (Action::PrevBookmark, "shift-f2"),
+    (Action::JumpBack, "ctrl-o"),
+    (Action::JumpForward, "alt-i"),
+    (Action::GoToDefinition, "alt-."),
+    (Action::GoToDefinition, "ctrl-x ctrl-]"),
+    (Action::ToggleCounterpart, "alt-o"),
+    (Action::FoldBlock, "ctrl-x ["),
+    (Action::UnfoldBlock, "ctrl-x ]"),
+    (Action::AddCursorAtNextMatch, "alt-d"),
+    (Action::SpellSuggest, "alt-$"),
+    (Action::ScrollUp, "alt-up"),
+    (Action::ScrollDown, "alt-down"),
+    (Action::ScrollLeft, "alt-left"),
+    (Action::ScrollRight, "alt-right"),
+];
+
+/// キーの指定（"ctrl-p" や "alt-up" などの形式）
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(crate) struct KeySpec {
+    pub(crate) code: KeyCode,
+    pub(crate) modifiers: KeyModifiers,
+}
+
+impl KeySpec {
+    /// "ctrl-p" / "alt-up" / "super-s" などをパース
+    pub(crate) fn parse(spec: &str) -> Option<Self> {
+        let spec = spec.trim().to_lowercase();
+        // "ctrl--" のようにマイナス自体を指定する場合
+        let (mods, key) = if spec == "-" {
+            ("", "-")
+        } else if let Some(mods) = spec.strip_suffix("--") {
+            (mods, "-")
+        } else {
+            spec.rsplit_once('-').unwrap_or(("", spec.as_str()))
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        for m in mods.split('-').filter(|m| !m.is_empty()) {
+            modifiers |= match m {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                "super" | "cmd" => KeyModifiers::SUPER,
+                _ => return None,
+            };
+        }
+
+        let code = match key {
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "enter" | "return" => KeyCode::Enter,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "space" => KeyCode::Char(' '),
+            _ => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => {
+                        let n = key.strip_prefix('f')?.parse::<u8>().ok()?;
+                        if !(1..=12).contains(&n) {
+                            return None;
+                        }
+                        KeyCode::F(n)
+                    }
+                }
+            }
+        };
+        Some(Self { code, modifiers })
+    }
+
+    /// キーイベントを比較用に正規化（大文字はshift付きの小文字として扱う）
+    pub(crate) fn from_event(key: &KeyEvent) -> Self {
+        let mut modifiers = key.modifiers
+            & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT | KeyModifiers::SUPER);
+        let code = match key.code {
+            KeyCode::Char(c) if c.is_uppercase() => {
+                modifiers |= KeyModifiers::SHIFT;
+                KeyCode::Char(c.to_lowercase().next().unwrap_or(c))
+            }
+            KeyCode::Char(c) => {
+                // "+" など、shiftで入力される記号はshiftを無視
+                if !c.is_alphabetic() {
+                    modifiers.remove(KeyModifiers::SHIFT);
+                }
+                KeyCode::Char(c)
+            }
+            code => code,
+        };
+        Self { code, modifiers }
+    }
+}
+
+/// キーの連続入力をパース（"ctrl-x ctrl-p" のように空白区切り）
+pub(crate) fn parse_key_sequence(spec: &str) -> Option<Vec<KeySpec>> {
+    let keys = spec.split_whitespace().map(KeySpec::parse).collect::<Option<Vec<_>>>()?;
+    if keys.is_empty() {
+        None
+    } else {
+        Some(keys)
+    }
+}
+
+impl std::fmt::Display for KeySpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "ctrl-"),
+            (KeyModifiers::ALT, "alt-"),
+            (KeyModifiers::SHIFT, "shift-"),
+            (KeyModifiers::SUPER, "super-"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                f.write_str(name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "f{}", n),
+            KeyCode::Up => f.write_str("up"),
+            KeyCode::Down => f.write_str("down"),
+            KeyCode::Left => f.write_str("left"),
+            KeyCode::Right => f.write_str("right"),
+            KeyCode::Enter => f.write_str("enter"),
+            KeyCode::Backspace => f.write_str("backspace"),
+            KeyCode::Delete => f.write_str("delete"),
+            KeyCode::Esc => f.write_str("esc"),
+            KeyCode::Tab => f.write_str("tab"),
+            KeyCode::Home => f.write_str("home"),
+            KeyCode::End => f.write_str("end"),
+            KeyCode::PageUp => f.write_str("pageup"),
+            KeyCode::PageDown => f.write_str("pagedown"),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// 設定ファイルのキー指定（1つまたは複数）
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum KeyBindingConfig {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyBindingConfig {
+    pub(crate) fn specs(&self) -> Vec<&str> {
+        match self {
+            KeyBindingConfig::One(spec) => vec![spec.as_str()],
+            KeyBindingConfig::Many(specs) => specs.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+/// キー入力に対応するアクションの検索結果
+pub(crate) enum KeyLookup {
+    Action(Action),
+    /// 複数キーの入力途中
+    Prefix,
+    Unbound,
+}
+
+/// キー → アクションの対応表
+pub(crate) struct Keymap {
+    pub(crate) bindings: HashMap<Vec<KeySpec>, Action>,
+}
+
+impl Keymap {
+    /// デフォルトに設定ファイルの指定を重ねて作成（不正なアクション名・キー指定は無視）
+    pub(crate) fn new(custom: &HashMap<String, KeyBindingConfig>) -> Self {
+        let mut bindings = HashMap::new();
+        for &(action, spec) in DEFAULT_KEYBINDINGS {
+            if custom.contains_key(action.name()) {
+                continue;
+            }
+            if let Some(keys) = parse_key_sequence(spec) {
+                bindings.insert(keys, action);
+            }
+        }
+        for (name, config) in custom {
+            let Some(action) = Action::from_name(name) else {
+                continue;
+            };
+            for keys in config.specs().into_iter().filter_map(parse_key_sequence) {
+                bindings.insert(keys, action);
+            }
+        }
+        Self { bindings }
+    }
+
+    /// 入力済みのキー列に対応するアクション（shift+矢印などが未割り当てならshiftなしで探す）
+    pub(crate) fn lookup(&self, keys: &[KeySpec]) -> KeyLookup {
+        if let Some(&action) = self.bindings.get(keys) {
+            return KeyLookup::Action(action);
+        }
+        if let Some((last, rest)) = keys.split_last() {
+            if !matches!(last.code, KeyCode::Char(_)) && last.modifiers.contains(KeyModifiers::SHIFT) {
+                let mut unshifted = rest.to_vec();
+                unshifted.push(KeySpec { code: last.code, modifiers: last.modifiers - KeyModifiers::SHIFT });
+                if let Some(&action) = self.bindings.get(&unshifted) {
+                    return KeyLookup::Action(action);
+                }
+            }
+        }
+        if self.bindings.keys().any(|k| k.len() > keys.len() && k.starts_with(keys)) {
+            KeyLookup::Prefix
+        } else {
+            KeyLookup::Unbound
+        }
+    }
+
+    /// 単独のキーイベントに対応するアクション
+    pub(crate) fn action_for(&self, key: &KeyEvent) -> Option<Action> {
+        match self.lookup(&[KeySpec::from_event(key)]) {
+            KeyLookup::Action(action) => Some(action),
+            _ => None,
+        }
+    }
+
+    /// アクションに割り当てられたキー（短いもの順）
+    pub(crate) fn keys_for(&self, action: Action) -> Vec<String> {
+        let mut keys: Vec<String> = self.bindings.iter()
+            .filter(|(_, &a)| a == action)
+            .map(|(keys, _)| keys.iter().map(|k| k.to_string()).collect::<Vec<_>>().join(" "))
+            .collect();
+        keys.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+        keys
+    }
+}
@@ -0,0 +1,17 @@
+//! simplideのエディタ本体（main.rsは端末の初期化とイベントループだけを行う）
+
+mod ansi;
+pub mod app;
+mod buffer;
+pub mod config;
+mod file_finder;
+pub mod highlight;
+mod image_view;
+mod keymap;
+mod spell;
+mod terminal;
+pub mod ui;
+
+pub use app::App;
+pub use config::Config;
+pub use highlight::{Language, SyntaxHighlighter};
@@ -0,0 +1,247 @@
+//! コメントとMarkdownの本文のスペルチェック（単語リストまたはhunspell形式の.dicを辞書に使う）
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tree_sitter_highlight::Highlight;
+
+use crate::highlight::HIGHLIGHT_NAMES;
+
+/// 辞書を設定で指定しなかったときに探すファイル
+const DEFAULT_DICTIONARIES: &[&str] = &[
+    "/usr/share/dict/words",
+    "/usr/share/hunspell/en_US.dic",
+    "/usr/share/myspell/en_US.dic",
+    "/usr/share/myspell/dicts/en_US.dic",
+];
+
+/// 修正候補の最大数
+const MAX_SUGGESTIONS: usize = 8;
+
+/// 修正候補にする単語の最大の編集距離
+const MAX_DISTANCE: usize = 2;
+
+/// 辞書にない単語の語尾変化を戻して引き直す規則（語尾, 戻した語尾）
+const SUFFIXES: &[(&str, &str)] = &[
+    ("'s", ""),
+    ("ies", "y"),
+    ("ied", "y"),
+    ("es", ""),
+    ("s", ""),
+    ("ed", ""),
+    ("ed", "e"),
+    ("ing", ""),
+    ("ing", "e"),
+    ("ly", ""),
+    ("er", ""),
+    ("est", ""),
+];
+
+/// コメントのハイライト名
+const COMMENT_CAPTURE: &str = "comment";
+
+/// Markdownで本文とみなすハイライト名（ハイライトのない文字のほか、見出しと強調）
+const PROSE_CAPTURES: &[&str] = &["text.title", "text.emphasis", "text.strong", "markup.heading"];
+
+pub(crate) struct Dictionary {
+    words: HashSet<String>,
+}
+
+impl Dictionary {
+    /// 辞書を読み込む（pathの指定がなければ標準の場所を探す）。個人辞書があればその単語も加える
+    pub(crate) fn load(path: Option<&Path>, personal: Option<&Path>) -> Option<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => DEFAULT_DICTIONARIES.iter().map(PathBuf::from).find(|p| p.is_file())?,
+        };
+        let mut words = HashSet::new();
+        add_words(&mut words, &fs::read(path).ok()?);
+        if let Some(content) = personal.and_then(|p| fs::read(p).ok()) {
+            add_words(&mut words, &content);
+        }
+        Some(Self { words })
+    }
+
+    /// 辞書にある単語か（大文字小文字は区別せず、複数形や過去形などは元の形でも引く）
+    pub(crate) fn contains(&self, word: &str) -> bool {
+        let word = word.to_lowercase();
+        self.words.contains(&word)
+            || SUFFIXES.iter().any(|(suffix, replacement)| {
+                word.strip_suffix(suffix)
+                    .is_some_and(|stem| stem.len() >= 2 && self.words.contains(&format!("{}{}", stem, replacement)))
+            })
+    }
+
+    /// 編集距離の近い順の修正候補（先頭が大文字の単語には大文字で始まる候補を返す）
+    pub(crate) fn suggestions(&self, word: &str) -> Vec<String> {
+        let lower = word.to_lowercase();
+        let len = lower.chars().count();
+        let mut scored: Vec<(usize, &String)> = self.words.iter()
+            .filter(|candidate| candidate.chars().count().abs_diff(len) <= MAX_DISTANCE)
+            .filter_map(|candidate| {
+                let distance = edit_distance(&lower, candidate);
+                (distance > 0 && distance <= MAX_DISTANCE).then_some((distance, candidate))
+            })
+            .collect();
+        scored.sort();
+        let capitalized = word.starts_with(|c: char| c.is_uppercase());
+        scored.into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, candidate)| if capitalized { capitalize(candidate) } else { candidate.clone() })
+            .collect()
+    }
+
+    /// ソースの中で辞書にない単語の範囲（バイト位置）。capturesはバイトごとの最も内側のハイライトで、
+    /// コメント（Markdownでは本文。コードブロックやインラインコードは除く）だけを調べる
+    pub(crate) fn misspelled_ranges(&self, source: &str, captures: &[Option<Highlight>], markdown: bool) -> Vec<(usize, usize)> {
+        let checked = |byte: usize| match captures.get(byte) {
+            Some(None) => markdown,
+            Some(Some(highlight)) => HIGHLIGHT_NAMES.get(highlight.0).is_some_and(|name| {
+                if markdown {
+                    PROSE_CAPTURES.contains(name)
+                } else {
+                    *name == COMMENT_CAPTURE
+                }
+            }),
+            None => false,
+        };
+        word_ranges(source)
+            .into_iter()
+            .filter(|&(start, end)| {
+                let word = &source[start..end];
+                checked(start)
+                    && is_checked_word(word)
+                    && !self.contains(word)
+            })
+            .collect()
+    }
+}
+
+/// 辞書ファイルの単語を加える（hunspellの.dicの "/" 以降の付加情報と先頭の語数の行は無視）
+fn add_words(words: &mut HashSet<String>, content: &[u8]) {
+    for line in String::from_utf8_lossy(content).lines() {
+        let word = line.split(['/', '\t', ' ']).next().unwrap_or("");
+        if word.is_empty() || word.starts_with('#') || word.bytes().all(|b| b.is_ascii_digit()) {
+            continue;
+        }
+        words.insert(word.to_lowercase());
+    }
+}
+
+/// 調べる単語か（短い語、略語やCamelCaseのように途中に大文字がある語は除く）
+fn is_checked_word(word: &str) -> bool {
+    word.len() >= 3 && !word.chars().skip(1).any(|c| c.is_uppercase())
+}
+
+/// 単語の範囲（英字の並び、途中のアポストロフィを含む）。数字や `_`、英字以外の文字と
+/// 続いている語は識別子などとみなして除く
+pub(crate) fn word_ranges(text: &str) -> Vec<(usize, usize)> {
+    let bytes = text.as_bytes();
+    let attached = |b: Option<&u8>| b.is_some_and(|&b| b.is_ascii_digit() || b == b'_' || b >= 0x80);
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if !bytes[i].is_ascii_alphabetic() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < bytes.len()
+            && (bytes[i].is_ascii_alphabetic()
+                || (bytes[i] == b'\'' && bytes.get(i + 1).is_some_and(u8::is_ascii_alphabetic)))
+        {
+            i += 1;
+        }
+        let before = start.checked_sub(1).and_then(|p| bytes.get(p));
+        if !attached(before) && !attached(bytes.get(i)) {
+            ranges.push((start, i));
+        }
+    }
+    ranges
+}
+
+/// 挿入・削除・置換・隣り合う文字の入れ替えを1回と数える編集距離
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // 2行前・1行前・現在の行
+    let mut before: Vec<usize> = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1).min(current[j - 1] + 1).min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut previous, current);
+    }
+    previous[b.len()]
+}
+
+/// 先頭の文字を大文字にする
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::highlight::{Language, SyntaxHighlighter};
+
+    fn dictionary(words: &[&str]) -> Dictionary {
+        Dictionary { words: words.iter().map(|w| w.to_string()).collect() }
+    }
+
+    #[test]
+    fn word_ranges_skip_identifiers() {
+        let text = "don't parse foo_bar v2 日本語abc here";
+        let words: Vec<&str> = word_ranges(text).into_iter().map(|(s, e)| &text[s..e]).collect();
+        assert_eq!(words, vec!["don't", "parse", "here"]);
+    }
+
+    #[test]
+    fn contains_inflected_words() {
+        let dict = dictionary(&["file", "open", "copy"]);
+        assert!(dict.contains("Files"));
+        assert!(dict.contains("opened"));
+        assert!(dict.contains("copies"));
+        assert!(!dict.contains("fiel"));
+    }
+
+    #[test]
+    fn suggestions_by_distance() {
+        let dict = dictionary(&["the", "then", "they", "cursor"]);
+        assert_eq!(dict.suggestions("teh")[0], "the");
+        assert_eq!(dict.suggestions("Cursro"), vec!["Cursor"]);
+    }
+
+    fn misspelled(source: &str, language: Language) -> Vec<&str> {
+        let dict = dictionary(&["fix", "this", "the", "and", "code"]);
+        let (_, captures) = SyntaxHighlighter::new(&HashMap::new()).highlight_all(source, language);
+        dict.misspelled_ranges(source, &captures, language == Language::Markdown)
+            .into_iter()
+            .map(|(start, end)| &source[start..end])
+            .collect()
+    }
+
+    #[test]
+    fn only_comments_are_checked() {
+        assert_eq!(misspelled("// fix thsi\nlet thsi = tpye;\n", Language::Rust), vec!["thsi"]);
+    }
+
+    #[test]
+    fn markdown_code_is_skipped() {
+        let source = "# Fix thsi\n\nthe `cdoe` and *thsi*\n\n```rust\nlet cdoe: Tpye = mdule::vaule;\n```\n";
+        assert_eq!(misspelled(source, Language::Markdown), vec!["thsi", "thsi"]);
+    }
+}
@@ -0,0 +1,293 @@
+//! 組み込みターミナル
+
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::app::App;
+
+/// 組み込みターミナルに保持する出力の最大行数
+pub(crate) const TERMINAL_SCROLLBACK: usize = 1000;
+
+/// 組み込みターミナル（PTY上のシェルの出力を行単位で保持する簡易版）
+pub(crate) struct TerminalPane {
+    pub(crate) master: Box<dyn portable_pty::MasterPty + Send>,
+    pub(crate) writer: Box<dyn Write + Send>,
+    pub(crate) _child: Box<dyn portable_pty::Child + Send + Sync>,
+    pub(crate) output_rx: Receiver<Vec<u8>>,
+    pub(crate) screen: TerminalScreen,
+    pub(crate) size: (u16, u16),
+}
+
+/// シェルの出力を反映した画面（行単位）
+pub(crate) struct TerminalScreen {
+    pub(crate) lines: Vec<String>,
+    /// 最終行の書き込み位置（文字数）
+    pub(crate) cursor_col: usize,
+    /// 途中で区切れたUTF-8のバイト列
+    pending: Vec<u8>,
+    /// 解析途中のエスケープシーケンス
+    escape: Option<String>,
+}
+
+impl TerminalPane {
+    /// シェルを起動（出力は読み取りスレッドからチャネルで受け取る）
+    pub(crate) fn spawn(dir: &Path, rows: u16, cols: u16) -> Result<Self, String> {
+        let size = portable_pty::PtySize { rows, cols, pixel_width: 0, pixel_height: 0 };
+        let pair = portable_pty::native_pty_system().openpty(size).map_err(|e| e.to_string())?;
+        let mut cmd = portable_pty::CommandBuilder::new_default_prog();
+        cmd.cwd(dir);
+        // 色やカーソル移動には対応しないので、なるべく使わないよう伝える
+        cmd.env("TERM", "dumb");
+        let child = pair.slave.spawn_command(cmd).map_err(|e| e.to_string())?;
+        let mut reader = pair.master.try_clone_reader().map_err(|e| e.to_string())?;
+        let writer = pair.master.take_writer().map_err(|e| e.to_string())?;
+
+        let (tx, output_rx) = mpsc::channel::<Vec<u8>>();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            loop {
+                match io::Read::read(&mut reader, &mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        if tx.send(buf[..n].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            master: pair.master,
+            writer,
+            _child: child,
+            output_rx,
+            screen: TerminalScreen::default(),
+            size: (rows, cols),
+        })
+    }
+
+    /// 届いた出力を取り込む（シェルが終了していたらfalse）
+    pub(crate) fn poll(&mut self) -> bool {
+        loop {
+            match self.output_rx.try_recv() {
+                Ok(bytes) => self.screen.process(&bytes),
+                Err(mpsc::TryRecvError::Empty) => return true,
+                Err(mpsc::TryRecvError::Disconnected) => return false,
+            }
+        }
+    }
+
+    /// キー入力をシェルに送る
+    pub(crate) fn send_key(&mut self, key: &KeyEvent) {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        let bytes: Vec<u8> = match key.code {
+            KeyCode::Char(c) if ctrl && c.is_ascii_alphabetic() => vec![c.to_ascii_lowercase() as u8 & 0x1f],
+            KeyCode::Char(' ') if ctrl => vec![0],
+            KeyCode::Char(c) => {
+                let mut bytes = if alt { vec![0x1b] } else { Vec::new() };
+                bytes.extend_from_slice(c.to_string().as_bytes());
+                bytes
+            }
+            KeyCode::Enter => b"\r".to_vec(),
+            KeyCode::Backspace => vec![0x7f],
+            KeyCode::Tab => b"\t".to_vec(),
+            KeyCode::Esc => vec![0x1b],
+            KeyCode::Up => b"\x1b[A".to_vec(),
+            KeyCode::Down => b"\x1b[B".to_vec(),
+            KeyCode::Right => b"\x1b[C".to_vec(),
+            KeyCode::Left => b"\x1b[D".to_vec(),
+            KeyCode::Home => b"\x1b[H".to_vec(),
+            KeyCode::End => b"\x1b[F".to_vec(),
+            KeyCode::Delete => b"\x1b[3~".to_vec(),
+            KeyCode::PageUp => b"\x1b[5~".to_vec(),
+            KeyCode::PageDown => b"\x1b[6~".to_vec(),
+            _ => return,
+        };
+        let _ = self.writer.write_all(&bytes);
+        let _ = self.writer.flush();
+    }
+
+    /// ペーストされたテキストをシェルに送る（端末と同じく改行は復帰にする）
+    pub(crate) fn send_text(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\r").replace('\n', "\r");
+        let _ = self.writer.write_all(text.as_bytes());
+        let _ = self.writer.flush();
+    }
+
+    /// 表示領域に合わせてPTYのサイズを変更
+    pub(crate) fn resize(&mut self, rows: u16, cols: u16) {
+        if self.size != (rows, cols) && rows > 0 && cols > 0 {
+            self.size = (rows, cols);
+            let _ = self.master.resize(portable_pty::PtySize { rows, cols, pixel_width: 0, pixel_height: 0 });
+        }
+    }
+}
+
+impl Default for TerminalScreen {
+    fn default() -> Self {
+        Self {
+            lines: vec![String::new()],
+            cursor_col: 0,
+            pending: Vec::new(),
+            escape: None,
+        }
+    }
+}
+
+impl TerminalScreen {
+    /// 出力を行に反映（改行・復帰・バックスペース・行末消去のみ解釈し、他の制御シーケンスは捨てる）
+    pub(crate) fn process(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+        // 不正なバイトは U+FFFD にして捨て、末尾で途切れた文字だけを次の出力まで残す
+        let mut text = String::new();
+        loop {
+            let (valid, invalid_len) = match std::str::from_utf8(&self.pending) {
+                Ok(valid) => (valid.len(), None),
+                Err(e) => (e.valid_up_to(), e.error_len()),
+            };
+            text.push_str(&String::from_utf8_lossy(&self.pending[..valid]));
+            match invalid_len {
+                Some(len) => {
+                    text.push(char::REPLACEMENT_CHARACTER);
+                    self.pending.drain(..valid + len);
+                }
+                None => {
+                    self.pending.drain(..valid);
+                    break;
+                }
+            }
+        }
+
+        for c in text.chars() {
+            if let Some(ref mut escape) = self.escape {
+                escape.push(c);
+                let finished = match escape.chars().next() {
+                    // CSI: 英字などの終端文字まで
+                    Some('[') => escape.len() > 1 && ('@'..='~').contains(&c),
+                    // OSC: BELまで
+                    Some(']') => c == '\x07' || escape.ends_with("\x1b\\"),
+                    _ => true,
+                };
+                if finished {
+                    if escape.starts_with('[') && c == 'K' {
+                        // 行末まで消去
+                        let line = self.lines.last_mut().expect("terminal has a line");
+                        *line = line.chars().take(self.cursor_col).collect();
+                    }
+                    self.escape = None;
+                }
+                continue;
+            }
+            match c {
+                '\x1b' => self.escape = Some(String::new()),
+                '\n' => {
+                    self.lines.push(String::new());
+                    self.cursor_col = 0;
+                    if self.lines.len() > TERMINAL_SCROLLBACK {
+                        self.lines.remove(0);
+                    }
+                }
+                '\r' => self.cursor_col = 0,
+                '\x08' => self.cursor_col = self.cursor_col.saturating_sub(1),
+                '\t' => {
+                    for _ in 0..(8 - self.cursor_col % 8) {
+                        self.put_char(' ');
+                    }
+                }
+                c if c.is_control() => {}
+                c => self.put_char(c),
+            }
+        }
+    }
+
+    /// 最終行の書き込み位置に文字を書く（既存の文字は上書き）
+    pub(crate) fn put_char(&mut self, c: char) {
+        let line = self.lines.last_mut().expect("terminal has a line");
+        let mut chars: Vec<char> = line.chars().collect();
+        if self.cursor_col < chars.len() {
+            chars[self.cursor_col] = c;
+        } else {
+            chars.resize(self.cursor_col, ' ');
+            chars.push(c);
+        }
+        *line = chars.into_iter().collect();
+        self.cursor_col += 1;
+    }
+}
+
+impl App {
+    /// ターミナルを表示してフォーカス（フォーカス中なら非表示にしてエディタに戻る）
+    pub(crate) fn toggle_terminal(&mut self) {
+        self.needs_clear = true;
+        if self.terminal_visible && self.terminal_focused {
+            self.terminal_visible = false;
+            self.terminal_focused = false;
+            return;
+        }
+        if self.terminal.is_none() {
+            // 大きさは最初の描画で表示領域に合わせる
+            match TerminalPane::spawn(self.current_dir(), 10, 80) {
+                Ok(terminal) => self.terminal = Some(terminal),
+                Err(e) => {
+                    self.set_status_message(format!("Failed to start terminal: {}", e));
+                    return;
+                }
+            }
+        }
+        self.terminal_visible = true;
+        self.terminal_focused = true;
+    }
+
+    /// ターミナルの出力を取り込む（シェルが終了したら閉じる）
+    pub(crate) fn poll_terminal(&mut self) {
+        if let Some(terminal) = self.terminal.as_mut() {
+            if !terminal.poll() {
+                self.terminal = None;
+                self.terminal_visible = false;
+                self.terminal_focused = false;
+                self.needs_clear = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen(chunks: &[&[u8]]) -> TerminalScreen {
+        let mut screen = TerminalScreen::default();
+        for chunk in chunks {
+            screen.process(chunk);
+        }
+        screen
+    }
+
+    #[test]
+    fn split_utf8_waits_for_the_rest() {
+        let bytes = "あい".as_bytes();
+        let mut s = screen(&[&bytes[..2]]);
+        assert_eq!(s.lines, vec![""]);
+        s.process(&bytes[2..]);
+        assert_eq!(s.lines, vec!["あい"]);
+    }
+
+    #[test]
+    fn invalid_utf8_is_replaced() {
+        let s = screen(&[b"a\xffb", b"\xc3(c\n", b"ok"]);
+        assert_eq!(s.lines, vec!["a\u{fffd}b\u{fffd}(c", "ok"]);
+    }
+
+    #[test]
+    fn carriage_return_and_erase_line() {
+        let s = screen(&[b"hello\rab\x1b[K\tx\x1b]0;title\x07"]);
+        assert_eq!(s.lines, vec!["ab      x"]);
+        assert_eq!(s.cursor_col, 9);
+    }
+}
@@ -0,0 +1,1407 @@
+//! 画面の描画と配色
+
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use ratatui::{
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph},
+};
+use ratatui_image::{
+    thread::ThreadImage,
+    Resize,
+};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::ansi::{ansi_to_spans, escape_mask};
+use crate::app::{format_file_size, App, ConfirmAction, FilterList, SidebarItem};
+use crate::buffer::{char_display_width, wrap_row_starts};
+use crate::config::ColorsConfig;
+use crate::highlight::Language;
+use crate::image_view::{IMAGE_RESIZE_DEBOUNCE, SPINNER_FRAMES, SPINNER_FRAME_DURATION};
+
+/// ミニマップの幅（枠を含む）と、表示するのに必要なエディタの幅
+const MINIMAP_WIDTH: u16 = 12;
+
+const MINIMAP_MIN_EDITOR_WIDTH: u16 = 60;
+
+/// ミニマップの1マスが表す表示幅
+const MINIMAP_COLUMNS_PER_CELL: usize = 8;
+
+/// ミニマップの1行を描くときに調べる最大行数（大きなファイルでも毎回全体を走査しない）
+const MINIMAP_SAMPLE_LINES: usize = 4;
+
+/// 絞り込み一覧の最大の高さ（枠と入力欄を含む）
+const FILTER_LIST_MAX_ROWS: usize = 20;
+
+/// 色付きで表示していないエスケープ文字の代わりに表示する記号
+const ESCAPE_SYMBOL: char = '␛';
+
+/// 画面の配色（指定がなければ周りの文字色のまま）
+pub struct UiColors {
+    pub(crate) git_branch: Style,
+    pub(crate) unsaved_marker: Style,
+    pub(crate) active_tab_bg: Color,
+    pub(crate) current_line_bg: Option<Color>,
+    /// ハイライトのない文字の色（明るい背景では黒）
+    pub(crate) text: Color,
+}
+
+impl UiColors {
+    /// 設定から作成（背景の明るさの指定がなければ端末に問い合わせる）
+    pub fn from_config(config: &ColorsConfig) -> Self {
+        let light_background = match config.background.as_deref() {
+            Some("light") => true,
+            Some("dark") => false,
+            _ => query_light_background().unwrap_or(false),
+        };
+        Self::new(config, light_background)
+    }
+
+    /// 設定と背景の明るさから作成
+    pub fn new(config: &ColorsConfig, light_background: bool) -> Self {
+        let parse = |name: &Option<String>| name.as_deref().and_then(|n| n.parse::<Color>().ok());
+        let fg = |name: &Option<String>| parse(name).map(|c| Style::default().fg(c)).unwrap_or_default();
+        Self {
+            git_branch: fg(&config.git_branch),
+            unsaved_marker: fg(&config.unsaved_marker),
+            active_tab_bg: parse(&config.active_tab_bg).unwrap_or(Color::DarkGray),
+            current_line_bg: parse(&config.current_line_bg),
+            text: if light_background { Color::Black } else { Color::White },
+        }
+    }
+}
+
+/// 背景色の問い合わせの応答を待つ時間
+const BACKGROUND_QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 端末に背景色を問い合わせて明るい背景かどうかを判定（応答がなければNone）
+pub(crate) fn query_light_background() -> Option<bool> {
+    let response = read_background_color_response(Instant::now() + BACKGROUND_QUERY_TIMEOUT)?;
+    parse_background_is_light(&response)
+}
+
+/// OSC 11で背景色を問い合わせて応答を読み込む（raw mode中に呼ぶ）。
+/// 期限を過ぎたら読み込みをやめ、後のキー入力を横取りしないよう待ち続けるスレッドは残さない
+#[cfg(unix)]
+pub(crate) fn read_background_color_response(deadline: Instant) -> Option<Vec<u8>> {
+    // DSR（ESC [5n）はどの端末も応答するので、OSC 11に未対応でも読み込みが終わる
+    let mut stdout = io::stdout();
+    stdout.write_all(b"\x1b]11;?\x07\x1b[5n").ok()?;
+    stdout.flush().ok()?;
+
+    let mut response = Vec::new();
+    let mut buf = [0u8; 64];
+    while !response.windows(4).any(|w| w == b"\x1b[0n") {
+        let timeout = deadline.checked_duration_since(Instant::now())?.as_millis();
+        let mut pollfd = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+        // SAFETY: pollfdは1要素の有効な配列
+        if unsafe { libc::poll(&mut pollfd, 1, timeout.min(libc::c_int::MAX as u128) as libc::c_int) } <= 0 {
+            return None;
+        }
+        // 標準入力のバッファに応答の後のキー入力を溜めないよう、読める分だけを直接読む
+        // SAFETY: bufは書き込み可能な長さbuf.len()の領域
+        let read = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) };
+        if read <= 0 {
+            return None;
+        }
+        response.extend_from_slice(&buf[..read as usize]);
+    }
+    Some(response)
+}
+
+/// 期限付きで標準入力を待てない環境では問い合わせない
+#[cfg(not(unix))]
+pub(crate) fn read_background_color_response(_deadline: Instant) -> Option<Vec<u8>> {
+    None
+}
+
+/// OSC 11の応答（`rgb:RRRR/GGGG/BBBB`）の輝度から明るい背景かどうかを判定
+pub(crate) fn parse_background_is_light(response: &[u8]) -> Option<bool> {
+    let text = String::from_utf8_lossy(response);
+    let rgb = &text[text.find("rgb:")? + 4..];
+    let end = rgb.find(['\x07', '\x1b']).unwrap_or(rgb.len());
+    let components: Vec<f32> = rgb[..end]
+        .split('/')
+        .map(|c| {
+            let max = 16u32.checked_pow(c.len() as u32)? - 1;
+            u32::from_str_radix(c, 16).ok().map(|v| v as f32 / max as f32)
+        })
+        .collect::<Option<_>>()?;
+    let [r, g, b] = components[..] else {
+        return None;
+    };
+    Some(0.2126 * r + 0.7152 * g + 0.0722 * b > 0.5)
+}
+
+/// 行の表示幅がガイドの位置に届かなければ空白で埋めてガイドの縦線を追加（文字と重なる行には引かない）
+pub(crate) fn push_wrap_guide(line: &mut Line<'static>, guide_x: usize) {
+    let width = line.width();
+    if width <= guide_x {
+        line.spans.push(Span::raw(" ".repeat(guide_x - width)));
+        line.spans.push(Span::styled("│", Style::default().fg(Color::DarkGray)));
+    }
+}
+
+/// カーソル位置の単語の候補の一覧（word_xに揃えてカーソルの下、入らなければ上に表示）
+pub(crate) fn render_candidates(frame: &mut ratatui::Frame, candidates: &[String], selected: usize, word_x: u16, cursor_y: u16) {
+    let area = frame.area();
+    let max_width = candidates.iter().map(|c| c.width()).max().unwrap_or(0);
+    let popup_width = (max_width as u16 + 4).min(area.width);
+    let popup_height = (candidates.len() as u16).min(8) + 2;
+    let popup_x = word_x.saturating_sub(2)
+        .min(area.x + area.width.saturating_sub(popup_width));
+    let popup_y = if cursor_y + 1 + popup_height <= area.y + area.height {
+        cursor_y + 1
+    } else {
+        cursor_y.saturating_sub(popup_height)
+    };
+    let popup_area = Rect::new(popup_x, popup_y, popup_width, popup_height.min(area.height));
+    let list_height = popup_area.height.saturating_sub(2) as usize;
+    let offset = (selected + 1).saturating_sub(list_height);
+    let items: Vec<ListItem> = candidates.iter()
+        .enumerate()
+        .skip(offset)
+        .take(list_height)
+        .map(|(i, candidate)| {
+            let style = if i == selected {
+                Style::default().fg(Color::White).bg(Color::Blue)
+            } else {
+                Style::default()
+            };
+            ListItem::new(format!(" {} ", candidate)).style(style)
+        })
+        .collect();
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(List::new(items).block(Block::default().borders(Borders::ALL)), popup_area);
+}
+
+/// 絞り込み一覧を画面中央に描画（itemsは左側の名前と右側の補足）
+pub(crate) fn render_filter_list(frame: &mut ratatui::Frame, title: &str, list: &FilterList, items: &[(String, String)]) {
+    let area = frame.area();
+    let popup_width = 60u16.min(area.width);
+    let popup_height = (items.len() as u16 + 3).clamp(4, FILTER_LIST_MAX_ROWS as u16).min(area.height);
+    let popup_area = Rect::new(
+        area.x + (area.width.saturating_sub(popup_width)) / 2,
+        area.y + (area.height.saturating_sub(popup_height)) / 2,
+        popup_width,
+        popup_height,
+    );
+    let block = Block::default().title(title).borders(Borders::ALL);
+    let inner = block.inner(popup_area);
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(block, popup_area);
+
+    if inner.height == 0 {
+        return;
+    }
+    let input = Paragraph::new(format!("> {}", list.query));
+    frame.render_widget(input, Rect::new(inner.x, inner.y, inner.width, 1));
+    frame.set_cursor_position((
+        (inner.x + 2 + list.query.width() as u16).min(inner.x + inner.width.saturating_sub(1)),
+        inner.y,
+    ));
+
+    // 選択中の項目が見えるようにスクロール
+    let list_height = inner.height.saturating_sub(1) as usize;
+    let offset = (list.selected + 1).saturating_sub(list_height);
+    let list_items: Vec<ListItem> = items.iter()
+        .enumerate()
+        .skip(offset)
+        .take(list_height)
+        .map(|(i, (name, detail))| {
+            let padding = (inner.width as usize).saturating_sub(name.width() + detail.width() + 2);
+            let style = if i == list.selected {
+                Style::default().fg(Color::White).bg(Color::Blue)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Line::from(vec![
+                Span::raw(format!(" {}{}", name, " ".repeat(padding))),
+                Span::styled(detail.clone(), Style::default().fg(Color::Gray)),
+                Span::raw(" "),
+            ]))
+            .style(style)
+        })
+        .collect();
+    let list_area = Rect::new(inner.x, inner.y + 1, inner.width, list_height as u16);
+    frame.render_widget(List::new(list_items), list_area);
+}
+
+/// 画面全体を描画
+pub fn draw(frame: &mut ratatui::Frame, app: &mut App) {
+    // ターミナルが小さすぎる場合はスキップ
+    let area = frame.area();
+    if area.width < 10 || area.height < 5 {
+        let msg = Paragraph::new("Terminal too small");
+        frame.render_widget(msg, area);
+        return;
+    }
+
+    // 非表示のときはエディタを全幅に
+    let sidebar_percent = if app.sidebar_visible { app.sidebar_width_percent } else { 0 };
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(sidebar_percent),
+            Constraint::Percentage(100 - sidebar_percent),
+        ])
+        .split(area);
+
+    // チャンクが不足している場合はスキップ
+    if chunks.len() < 2 {
+        return;
+    }
+
+    // 非表示のときはマウス操作の対象にしない
+    app.sidebar_area = if app.sidebar_visible { chunks[0] } else { Rect::default() };
+
+    // ターミナル表示中は下部に分割
+    let main_area = if app.terminal_visible {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
+            .split(chunks[1]);
+        app.terminal_area = rows[1];
+        rows[0]
+    } else {
+        app.terminal_area = Rect::default();
+        chunks[1]
+    };
+
+    // タブがある場合はエディタ領域を分割
+    let (tab_area, editor_area) = if !app.tabs.is_empty() {
+        let editor_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1),  // タブバー
+                Constraint::Min(0),      // エディタ
+            ])
+            .split(main_area);
+        if editor_chunks.len() < 2 {
+            (None, main_area)
+        } else {
+            (Some(editor_chunks[0]), editor_chunks[1])
+        }
+    } else {
+        (None, main_area)
+    };
+    app.tab_area = tab_area.unwrap_or(Rect::default());
+
+    // 分割表示中はフォーカスしていないペインを先に描画
+    let editor_area = if let Some(mut other) = app.other_pane.take() {
+        let pane_chunks = Layout::default()
+            .direction(app.split_direction)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(editor_area);
+        other.editor_area = pane_chunks[1 - app.focused_pane];
+        app.other_pane = Some(other);
+        app.sync_other_pane();
+
+        if let Some(mut other) = app.other_pane.take() {
+            std::mem::swap(&mut app.pane, &mut other);
+            let area = app.pane.editor_area;
+            let block = Block::default()
+                .title(Line::from(app.pane_title_spans()))
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::DarkGray));
+            if app.pane.is_image_mode {
+                // 画像はフォーカス中のペインにだけ表示
+                frame.render_widget(Paragraph::new("(image)").block(block), area);
+            } else {
+                let lines = app.inactive_pane_lines(
+                    area.height.saturating_sub(2) as usize,
+                    area.width.saturating_sub(2) as usize,
+                );
+                frame.render_widget(Paragraph::new(lines).block(block), area);
+            }
+            std::mem::swap(&mut app.pane, &mut other);
+            app.other_pane = Some(other);
+        }
+        pane_chunks[app.focused_pane]
+    } else {
+        editor_area
+    };
+    // ミニマップ（テキスト表示中でエディタの幅が十分なときだけ右端に表示）
+    let show_minimap = app.minimap
+        && app.diff_view.is_none()
+        && !app.pane.is_image_mode
+        && editor_area.width >= MINIMAP_MIN_EDITOR_WIDTH;
+    let editor_area = if show_minimap {
+        let text_width = editor_area.width - MINIMAP_WIDTH;
+        app.minimap_area = Rect::new(editor_area.x + text_width, editor_area.y, MINIMAP_WIDTH, editor_area.height);
+        Rect { width: text_width, ..editor_area }
+    } else {
+        app.minimap_area = Rect::default();
+        editor_area
+    };
+    app.pane.editor_area = editor_area;
+
+    // タブバーの描画
+    if let Some(tab_rect) = tab_area {
+        let mut tab_spans: Vec<Span> = Vec::new();
+
+        for path in app.tabs.iter() {
+            let file_name = path.file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| "New".to_string());
+
+            // このタブが未保存かチェック
+            let is_unsaved = app.is_path_unsaved(path);
+
+            let is_active = Some(path) == app.pane.file_path.as_ref();
+
+            let style = if is_active {
+                Style::default().bg(app.colors.active_tab_bg).fg(Color::White)
+            } else {
+                Style::default().fg(Color::Gray)
+            };
+
+            tab_spans.push(Span::styled(format!(" {}", file_name), style));
+            if is_unsaved {
+                tab_spans.push(Span::styled("*", style.patch(app.colors.unsaved_marker)));
+            }
+            tab_spans.push(Span::styled(" ", style));
+            tab_spans.push(Span::raw(" ")); // タブ間のスペース
+        }
+
+        let tab_line = Line::from(tab_spans);
+        let tab_bar = Paragraph::new(vec![tab_line]);
+        frame.render_widget(tab_bar, tab_rect);
+    }
+
+    // サイドバー（スクロール対応）
+    let list_width = chunks[0].width.saturating_sub(2) as usize;
+    let visible_height = chunks[0].height.saturating_sub(2) as usize;
+    let sidebar_items = app.sidebar_items();
+    let total_items = sidebar_items.len();
+
+    // 横スクロールを適用するヘルパー
+    let apply_h_scroll = |s: &str, scroll_x: usize| -> String {
+        let chars: Vec<char> = s.chars().collect();
+        if scroll_x >= chars.len() {
+            String::new()
+        } else {
+            chars[scroll_x..].iter().collect()
+        }
+    };
+
+    let items: Vec<ListItem> = sidebar_items.iter()
+        .enumerate()
+        .skip(app.sidebar_scroll)
+        .take(visible_height)
+        .map(|(idx, item)| {
+            let label = app.sidebar_item_label(item, list_width);
+            let mut list_item = ListItem::new(Line::from(apply_h_scroll(&label, app.sidebar_scroll_x)));
+            if matches!(item, SidebarItem::Root(_)) {
+                list_item = list_item.style(Style::default().add_modifier(Modifier::BOLD));
+            }
+            // フォーカス中は選択中の項目を強調
+            if app.sidebar_focused && idx == app.sidebar_selected {
+                list_item = list_item.style(Style::default().fg(Color::White).bg(Color::Blue));
+            }
+            list_item
+        })
+        .collect();
+
+    let dir_name = if app.roots.len() > 1 {
+        "Workspace".to_string()
+    } else {
+        let root = &app.roots[0];
+        match root.current_dir.strip_prefix(&root.path) {
+            Ok(rel) if !rel.as_os_str().is_empty() => format!("{}/{}", root.name(), rel.to_string_lossy()),
+            Ok(_) => root.name(),
+            Err(_) => root.current_dir.to_string_lossy().to_string(),
+        }
+    };
+    let title = if total_items > visible_height {
+        format!("{} [{}/{}]",
+            dir_name,
+            app.sidebar_scroll + 1,
+            total_items.saturating_sub(visible_height) + 1)
+    } else {
+        dir_name
+    };
+
+    let border_style = if app.sidebar_focused {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default()
+    };
+    let sidebar = List::new(items)
+        .block(Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_style(border_style));
+    frame.render_widget(sidebar, chunks[0]);
+
+    // 絞り込み文字列（下枠に表示）
+    if app.sidebar_focused && app.sidebar_visible && chunks[0].height >= 2 {
+        let filter_area = Rect::new(
+            chunks[0].x + 1,
+            chunks[0].y + chunks[0].height - 1,
+            chunks[0].width.saturating_sub(2),
+            1,
+        );
+        let filter_bar = Paragraph::new(format!("/{}", app.sidebar_filter))
+            .style(Style::default().fg(Color::Cyan));
+        frame.render_widget(filter_bar, filter_area);
+    }
+
+    // エディタ
+    if let Some(ref diff) = app.diff_view {
+        // 差分表示
+        let block = Block::default()
+            .title(format!("Diff: {} (+{} -{}) [Esc: Close]", app.file_name(), diff.insertions, diff.deletions))
+            .borders(Borders::ALL);
+        let visible_height = editor_area.height.saturating_sub(2) as usize;
+        let lines: Vec<Line> = diff.lines.iter().skip(diff.scroll).take(visible_height).cloned().collect();
+        frame.render_widget(Paragraph::new(lines).block(block), editor_area);
+    } else if app.pane.is_image_mode {
+        // 画像モード
+        // 画像情報（サイズ・形式・ファイルサイズ・拡大率）
+        let mut image_info = Vec::new();
+        if let Some((width, height)) = app.image_dimensions {
+            image_info.push(format!("{}x{}", width, height));
+        }
+        if let Some(ext) = app.pane.file_path.as_ref().and_then(|p| p.extension()) {
+            image_info.push(ext.to_string_lossy().to_uppercase());
+        }
+        if let Some(size) = app.image_file_size {
+            image_info.push(format_file_size(size));
+        }
+        if app.image_view.is_zoomed() {
+            image_info.push(format!("{:.0}%", app.image_view.zoom * 100.0));
+        }
+        let block = Block::default()
+            .title(format!("{} ({}) [+/-:Zoom 0:Fit Ctrl-C: Quit]", app.file_name(), image_info.join(", ")))
+            .borders(Borders::ALL);
+        let inner = block.inner(editor_area);
+        frame.render_widget(block, editor_area);
+
+        // 画像領域が十分な大きさの場合のみ描画
+        if inner.width > 0 && inner.height > 0 {
+            if let Some(started) = app.image_loading {
+                // ローディング中（スピナーを回す）
+                let frame_index = (started.elapsed().as_millis() / SPINNER_FRAME_DURATION.as_millis()) as usize;
+                let spinner = SPINNER_FRAMES[frame_index % SPINNER_FRAMES.len()];
+                let loading = Paragraph::new(format!("{} Loading... (Esc: Cancel)", spinner));
+                frame.render_widget(loading, inner);
+            } else if app.image_state.is_none() {
+                frame.render_widget(Paragraph::new("Loading cancelled"), inner);
+            } else if app.image_state.is_some() {
+                // 表示先が変わった場合は、しばらく変化がなくなるまでリサイズを要求しない
+                let zoomed = app.image_view.is_zoomed();
+                match app.image_resize_target {
+                    Some((z, area, _)) if z == zoomed && area == inner => {}
+                    _ => app.image_resize_target = Some((zoomed, inner, Instant::now())),
+                }
+                let settled = app.image_resize_target
+                    .is_some_and(|(_, _, changed_at)| changed_at.elapsed() >= IMAGE_RESIZE_DEBOUNCE);
+                if let Some(image_state) = app.image_state.as_mut().filter(|_| settled) {
+                    // 拡大中は切り抜いた範囲を表示領域いっぱいに拡大
+                    let resize = if zoomed {
+                        Resize::Scale(None)
+                    } else {
+                        Resize::Fit(None)
+                    };
+                    let image_widget = ThreadImage::default().resize(resize);
+                    frame.render_stateful_widget(image_widget, inner, image_state);
+                }
+            }
+        }
+    } else {
+        // テキストモード
+        let visible_height = editor_area.height.saturating_sub(2) as usize;
+        let visible_width = editor_area.width.saturating_sub(2) as usize;
+        let lines = app.get_highlighted_lines(visible_height, visible_width);
+
+        let editor_block = Block::default()
+            .title(Line::from([
+                app.pane_title_spans(),
+                vec![Span::raw(format!("{} [C-s:Save C-w:Close C-]:Tab C-c:Quit]", app.vim_mode_label()))],
+            ].concat()))
+            .borders(Borders::ALL);
+        let editor = Paragraph::new(lines).block(editor_block);
+        frame.render_widget(editor, editor_area);
+
+        if app.minimap_area.width > 0 {
+            let minimap_block = Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::DarkGray));
+            let inner = minimap_block.inner(app.minimap_area);
+            let minimap = app.minimap_lines(inner.width as usize, inner.height as usize);
+            frame.render_widget(Paragraph::new(minimap).block(minimap_block), app.minimap_area);
+        }
+
+        // カーソル表示（行番号と横スクロール、全角文字幅、折り返しを考慮）
+        let ln_width = app.line_number_width() as u16;
+        let (cursor_row, cursor_col) = app.visual_position(app.pane.cursor_line, app.pane.cursor_col).unwrap_or((0, 0));
+        let cursor_x = editor_area.x + 1 + ln_width + cursor_col as u16;
+        let cursor_y = editor_area.y + 1 + cursor_row as u16;
+
+        // カーソル位置を画面内に制限
+        let max_x = editor_area.x + editor_area.width.saturating_sub(1);
+        let max_y = editor_area.y + editor_area.height.saturating_sub(1);
+        let cursor_x = cursor_x.min(max_x);
+        let cursor_y = cursor_y.min(max_y);
+
+        // 検索バー
+        if app.search_mode {
+            if editor_area.height >= 2 {
+                let search_area = Rect::new(
+                    editor_area.x,
+                    editor_area.y + editor_area.height.saturating_sub(1),
+                    editor_area.width,
+                    1,
+                );
+                let search_text = format!("Search: {}", app.search_query);
+                let search_bar = Paragraph::new(search_text)
+                    .style(Style::default().bg(Color::DarkGray).fg(Color::White));
+                frame.render_widget(Clear, search_area);
+                frame.render_widget(search_bar, search_area);
+                // 検索オプションとマッチ数（現在のマッチ/総数）を右端に表示
+                let mut match_info = Vec::new();
+                if app.search_case_insensitive {
+                    match_info.push(Span::raw("[icase] "));
+                }
+                if !app.search_wrap {
+                    match_info.push(Span::raw("[nowrap] "));
+                }
+                if !app.search_query.is_empty() && app.search_matches.is_empty() {
+                    match_info.push(Span::styled("no matches ", Style::default().fg(Color::LightRed)));
+                } else if !app.search_query.is_empty() {
+                    match_info.push(Span::raw(format!("[{}/{}] ", app.search_index + 1, app.search_matches.len())));
+                }
+                if !match_info.is_empty() {
+                    let match_bar = Paragraph::new(Line::from(match_info)).alignment(Alignment::Right);
+                    frame.render_widget(match_bar, search_area);
+                }
+                // 検索バーにカーソルを表示（画面内に制限）
+                let search_cursor_x = (editor_area.x + 8 + app.search_query.len() as u16).min(max_x);
+                let search_cursor_y = editor_area.y + editor_area.height.saturating_sub(1);
+                frame.set_cursor_position((search_cursor_x, search_cursor_y));
+            }
+        // Vimのコマンドライン
+        } else if let Some(ref command) = app.vim_command {
+            if editor_area.height >= 2 {
+                let command_y = editor_area.y + editor_area.height.saturating_sub(1);
+                let command_area = Rect::new(editor_area.x, command_y, editor_area.width, 1);
+                let command_bar = Paragraph::new(format!(":{}", command))
+                    .style(Style::default().bg(Color::DarkGray).fg(Color::White));
+                frame.render_widget(command_bar, command_area);
+                let command_cursor_x = (editor_area.x + 1 + command.len() as u16).min(max_x);
+                frame.set_cursor_position((command_cursor_x, command_y));
+            }
+        // 行ジャンプなどの入力欄
+        } else if let Some(ref prompt) = app.prompt {
+            if editor_area.height >= 2 {
+                let prompt_y = editor_area.y + editor_area.height.saturating_sub(1);
+                let prompt_area = Rect::new(editor_area.x, prompt_y, editor_area.width, 1);
+                let label = prompt.kind.label();
+                let prompt_bar = Paragraph::new(format!("{}{}", label, prompt.input))
+                    .style(Style::default().bg(Color::DarkGray).fg(Color::White));
+                frame.render_widget(prompt_bar, prompt_area);
+                let prompt_cursor_x = (editor_area.x + (label.len() + prompt.input.len()) as u16).min(max_x);
+                frame.set_cursor_position((prompt_cursor_x, prompt_y));
+            }
+        } else {
+            frame.set_cursor_position((cursor_x, cursor_y));
+        }
+
+        // 入力補完の候補（単語の先頭に揃えてカーソルの下、入らなければ上に表示）
+        if let Some(ref completion) = app.completion {
+            let word_x = cursor_x.saturating_sub(completion.prefix.width() as u16);
+            render_candidates(frame, &completion.candidates, completion.selected, word_x, cursor_y);
+        }
+        // スペルの修正候補（補完と同じく単語の先頭に揃える）
+        if let Some(ref suggestion) = app.spell_suggestion {
+            let typed = app.cursor_to_char_idx().saturating_sub(suggestion.start) as u16;
+            render_candidates(frame, &suggestion.candidates, suggestion.selected, cursor_x.saturating_sub(typed), cursor_y);
+        }
+
+        // ステータスメッセージ
+        if let Some((ref message, _)) = app.status_message {
+            if editor_area.height >= 2 && !app.search_mode && app.vim_command.is_none() && app.prompt.is_none() {
+                let status_area = Rect::new(
+                    editor_area.x,
+                    editor_area.y + editor_area.height.saturating_sub(1),
+                    editor_area.width,
+                    1,
+                );
+                let status_bar = Paragraph::new(message.as_str())
+                    .style(Style::default().bg(Color::DarkGray).fg(Color::White));
+                frame.render_widget(Clear, status_area);
+                frame.render_widget(status_bar, status_area);
+            }
+        }
+
+        // 数引数の入力中表示
+        if let Some(arg) = app.prefix_arg {
+            if editor_area.height >= 2 {
+                let arg_area = Rect::new(
+                    editor_area.x,
+                    editor_area.y + editor_area.height.saturating_sub(1),
+                    editor_area.width,
+                    1,
+                );
+                let arg_bar = Paragraph::new(format!("C-u {}-", arg.count))
+                    .style(Style::default().bg(Color::DarkGray).fg(Color::White));
+                frame.render_widget(arg_bar, arg_area);
+            }
+        }
+
+        // 複数キーの入力途中表示
+        if !app.pending_keys.is_empty() && editor_area.height >= 2 {
+            let keys_area = Rect::new(
+                editor_area.x,
+                editor_area.y + editor_area.height.saturating_sub(1),
+                editor_area.width,
+                1,
+            );
+            let keys: Vec<String> = app.pending_keys.iter().map(|k| k.to_string()).collect();
+            let keys_bar = Paragraph::new(format!("{}-", keys.join(" ")))
+                .style(Style::default().bg(Color::DarkGray).fg(Color::White));
+            frame.render_widget(keys_bar, keys_area);
+        }
+
+        // コピーボタン表示
+        if let Some(btn_area) = app.copy_button_area {
+            if btn_area.x >= editor_area.x && btn_area.y >= editor_area.y
+                && btn_area.x + btn_area.width <= editor_area.x + editor_area.width
+                && btn_area.y + btn_area.height <= editor_area.y + editor_area.height
+            {
+                frame.render_widget(Clear, btn_area);
+                let copy_btn = Paragraph::new(" Copy ")
+                    .style(Style::default().bg(Color::Green).fg(Color::Black));
+                frame.render_widget(copy_btn, btn_area);
+            }
+        }
+    }
+
+    // 組み込みターミナル（最後の行から表示）
+    if app.terminal_visible {
+        let area = app.terminal_area;
+        let focused = app.terminal_focused;
+        if let Some(terminal) = app.terminal.as_mut() {
+            let border_style = if focused {
+                Style::default()
+            } else {
+                Style::default().fg(Color::DarkGray)
+            };
+            let block = Block::default()
+                .title("Terminal [C-x t: Hide]")
+                .borders(Borders::ALL)
+                .border_style(border_style);
+            let inner = block.inner(area);
+            terminal.resize(inner.height, inner.width);
+            let start = terminal.screen.lines.len().saturating_sub(inner.height as usize);
+            let lines: Vec<Line> = terminal.screen.lines[start..].iter().map(|l| Line::from(l.clone())).collect();
+            frame.render_widget(Clear, area);
+            frame.render_widget(Paragraph::new(lines).block(block), area);
+            if focused && inner.width > 0 && inner.height > 0 {
+                let row = (terminal.screen.lines.len() - 1 - start) as u16;
+                let col: usize = terminal.screen.lines.last()
+                    .map(|l| l.chars().take(terminal.screen.cursor_col).map(char_display_width).sum())
+                    .unwrap_or(0);
+                frame.set_cursor_position((
+                    (inner.x + col as u16).min(inner.x + inner.width - 1),
+                    inner.y + row,
+                ));
+            }
+        }
+    }
+
+    // サイドバーにフォーカス中は絞り込み文字列の末尾にカーソルを表示
+    if app.sidebar_focused && app.sidebar_area.height >= 2 {
+        let area = app.sidebar_area;
+        frame.set_cursor_position((
+            (area.x + 2 + app.sidebar_filter.width() as u16).min(area.x + area.width.saturating_sub(1)),
+            area.y + area.height - 1,
+        ));
+    }
+
+    // コマンドパレット
+    if let Some(ref palette) = app.command_palette {
+        let items: Vec<(String, String)> = app.palette_actions().into_iter()
+            .map(|action| {
+                let key = app.keymap.keys_for(action).into_iter().next().unwrap_or_default();
+                (action.title().to_string(), key)
+            })
+            .collect();
+        render_filter_list(frame, " Commands ", palette, &items);
+    }
+
+    // 最近のファイル一覧
+    if let Some(ref list) = app.recent_list {
+        let items: Vec<(String, String)> = app.recent_list_files().iter()
+            .map(|path| {
+                let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                let dir = path.parent().map(|d| app.display_path(d)).unwrap_or_default();
+                (name, dir)
+            })
+            .collect();
+        render_filter_list(frame, " Recent Files ", list, &items);
+    }
+
+    // ワークスペースのファイル検索（列挙中は件数の後に … を表示、見える範囲の項目だけ作る）
+    if let Some(ref finder) = app.file_finder {
+        let items: Vec<(String, String)> = finder.matches.iter()
+            .take(finder.list.selected + FILTER_LIST_MAX_ROWS)
+            .map(|&i| {
+                let display = Path::new(&finder.files[i].1);
+                let name = display.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                let dir = display.parent().map(|d| d.display().to_string()).unwrap_or_default();
+                (name, dir)
+            })
+            .collect();
+        let title = format!(
+            " Find File ({}/{}{}) ",
+            finder.matches.len(),
+            finder.files.len(),
+            if finder.done { "" } else { "…" },
+        );
+        render_filter_list(frame, &title, &finder.list, &items);
+    }
+
+    // 確認ダイアログ
+    if let Some(action) = app.confirm_dialog {
+        let dialog_width = 40u16;
+        let dialog_height = 5u16;
+        let area = frame.area();
+        let dialog_area = Rect::new(
+            area.x + (area.width.saturating_sub(dialog_width)) / 2,
+            area.y + (area.height.saturating_sub(dialog_height)) / 2,
+            dialog_width.min(area.width),
+            dialog_height.min(area.height),
+        );
+        let message = match action {
+            ConfirmAction::Quit => "  Quit? Unsaved changes will be lost.",
+            ConfirmAction::CloseTab => "  Close tab? Changes will be lost.",
+            ConfirmAction::Overwrite => "  File exists. Overwrite it?",
+        };
+        let dialog = Paragraph::new(vec![
+            Line::from(""),
+            Line::from(message),
+            Line::from("  (Y/N)"),
+        ])
+        .block(Block::default().title(" Confirm ").borders(Borders::ALL))
+        .style(Style::default().bg(Color::DarkGray));
+        frame.render_widget(Clear, dialog_area);
+        frame.render_widget(dialog, dialog_area);
+    }
+}
+
+impl App {
+    /// フォーカスしていないペインの表示行（選択・検索のハイライトなし）
+    pub(crate) fn inactive_pane_lines(&mut self, visible_height: usize, visible_width: usize) -> Vec<Line<'static>> {
+        let selection = self.selection.take();
+        let search_mode = std::mem::replace(&mut self.search_mode, false);
+        let current_line_bg = self.colors.current_line_bg.take();
+        let lines = self.get_highlighted_lines(visible_height, visible_width);
+        self.selection = selection;
+        self.search_mode = search_mode;
+        self.colors.current_line_bg = current_line_bg;
+        lines
+    }
+
+    /// タイトルに表示するブランチ名
+    pub(crate) fn git_branch_label(&self) -> String {
+        self.pane.git_branch.as_ref().map(|b| format!(" ({})", b)).unwrap_or_default()
+    }
+
+    /// ペインのタイトル（ファイル名、未保存マーク、ブランチ名）
+    pub(crate) fn pane_title_spans(&self) -> Vec<Span<'static>> {
+        let mut spans = vec![Span::raw(self.file_name())];
+        if self.is_unsaved() {
+            spans.push(Span::styled(" *", self.colors.unsaved_marker));
+        }
+        spans.push(Span::styled(self.git_branch_label(), self.colors.git_branch));
+        spans
+    }
+
+    pub(crate) fn update_cache(&mut self) {
+        if !self.pane.buffer_dirty {
+            return;
+        }
+
+        // sourceキャッシュを更新
+        self.pane.source_cache.clear();
+        for chunk in self.pane.buffer.chunks() {
+            self.pane.source_cache.push_str(chunk);
+        }
+
+        // 行オフセットキャッシュを構築し、最大行幅を計算（表示幅ベース）
+        self.pane.line_offsets.clear();
+        self.pane.line_offsets.push(0);
+        self.pane.max_line_width = 0;
+        let mut current_line_width = 0usize;
+        let mut byte_pos = 0usize;
+        for ch in self.pane.source_cache.chars() {
+            if ch == '\n' {
+                self.pane.max_line_width = self.pane.max_line_width.max(current_line_width);
+                byte_pos += ch.len_utf8();
+                self.pane.line_offsets.push(byte_pos);
+                current_line_width = 0;
+            } else if ch == '\t' {
+                // タブは4スペース相当として計算
+                current_line_width += 4;
+                byte_pos += 1;
+            } else {
+                // 表示幅を使用（全角=2, 半角=1）
+                current_line_width += ch.width().unwrap_or(1);
+                byte_pos += ch.len_utf8();
+            }
+        }
+        // 最終行（改行で終わらない場合）
+        self.pane.max_line_width = self.pane.max_line_width.max(current_line_width);
+
+        // ハイライトキャッシュを更新し、辞書にない単語を探す（コメント、Markdownでは本文だけを調べる）
+        self.pane.highlight_cache = None;
+        self.pane.spell_errors = Vec::new();
+        if let Some(lang) = self.pane.current_language {
+            if !self.pane.source_cache.is_empty() {
+                let (colors, captures) = self.syntax.highlight_all(&self.pane.source_cache, lang);
+                if let Some(dictionary) = &self.spell_dictionary {
+                    let markdown = lang == Language::Markdown;
+                    self.pane.spell_errors = dictionary.misspelled_ranges(&self.pane.source_cache, &captures, markdown);
+                }
+                self.pane.highlight_cache = Some(colors);
+            }
+        }
+
+        self.pane.buffer_dirty = false;
+    }
+
+    pub(crate) fn get_line_from_cache(&self, line_idx: usize) -> Option<(&str, usize)> {
+        if line_idx >= self.pane.line_offsets.len() {
+            return None;
+        }
+        let start = self.pane.line_offsets[line_idx];
+        let end = if line_idx + 1 < self.pane.line_offsets.len() {
+            self.pane.line_offsets[line_idx + 1]
+        } else {
+            self.pane.source_cache.len()
+        };
+        // 改行を除いた範囲
+        let text_end = if end > start && self.pane.source_cache.as_bytes().get(end - 1) == Some(&b'\n') {
+            end - 1
+        } else {
+            end
+        };
+        Some((&self.pane.source_cache[start..text_end], start))
+    }
+
+    pub(crate) fn line_number_width(&self) -> usize {
+        let total = self.pane.buffer.len_lines().max(1);
+        let digits = (total as f64).log10().floor() as usize + 1;
+        digits + 1 // +1 for space after number
+    }
+
+    /// 本文の表示幅（ボーダーと行番号を除く）
+    pub(crate) fn content_width(&self) -> usize {
+        (self.pane.editor_area.width.saturating_sub(2) as usize).saturating_sub(self.line_number_width())
+    }
+
+    /// 折り返して表示するか（ANSIの色付き表示中はエスケープシーケンスの幅が変わるので折り返さない）
+    pub(crate) fn wrapping(&self) -> bool {
+        self.wrap_lines && !self.pane.ansi_colors
+    }
+
+    /// 指定行の各表示行の開始文字インデックス（折り返し無効時は常に1行）
+    pub(crate) fn line_row_starts(&self, line_idx: usize) -> Vec<usize> {
+        if !self.wrapping() || line_idx >= self.pane.buffer.len_lines() {
+            return vec![0];
+        }
+        wrap_row_starts(self.pane.buffer.line(line_idx).chars(), self.content_width())
+    }
+
+    /// バッファ上の位置を表示位置（scroll_offsetからの表示行、本文内の表示列）に変換
+    pub(crate) fn visual_position(&self, line: usize, col: usize) -> Option<(usize, usize)> {
+        if line < self.pane.scroll_offset || line >= self.pane.buffer.len_lines() || self.pane.is_hidden(line) {
+            return None;
+        }
+        let line_slice = self.pane.buffer.line(line);
+        if self.pane.ansi_colors {
+            // 横スクロール位置からカーソルまでの、エスケープシーケンスを除いた幅
+            let mask = escape_mask(&line_slice.to_string());
+            let display_col = line_slice.chars()
+                .zip(mask)
+                .take(col)
+                .skip(self.pane.horizontal_scroll)
+                .filter(|&(_, hidden)| !hidden)
+                .map(|(c, _)| char_display_width(c))
+                .sum();
+            let row = self.pane.visible_rows_between(self.pane.scroll_offset, line);
+            return Some((row, display_col));
+        }
+        if !self.wrapping() {
+            let display_col: usize = line_slice.chars()
+                .take(col)
+                .map(|c| c.width().unwrap_or(1))
+                .sum();
+            let row = self.pane.visible_rows_between(self.pane.scroll_offset, line);
+            return Some((row, display_col.saturating_sub(self.pane.horizontal_scroll)));
+        }
+
+        let mut rows_before = 0;
+        let mut l = self.pane.scroll_offset;
+        while l < line {
+            rows_before += self.line_row_starts(l).len();
+            l = self.pane.next_visible_line(l);
+        }
+        let starts = self.line_row_starts(line);
+        let row = starts.iter().rposition(|&start| start <= col).unwrap_or(0);
+        let display_col = line_slice.chars()
+            .skip(starts[row])
+            .take(col.saturating_sub(starts[row]))
+            .map(char_display_width)
+            .sum();
+        Some((rows_before + row, display_col))
+    }
+
+    /// 本文内の表示列（横スクロール位置から）を文字インデックスに変換（ANSI表示中はエスケープシーケンスを飛ばす）
+    pub(crate) fn content_col_to_char_col(&self, line: usize, content_col: usize) -> usize {
+        if !self.pane.ansi_colors {
+            return self.pane.display_col_to_char_col(line, content_col + self.pane.horizontal_scroll);
+        }
+        let line_slice = self.pane.buffer.line(line);
+        let mask = escape_mask(&line_slice.to_string());
+        let mut col = self.pane.horizontal_scroll;
+        let mut width = 0;
+        for (ch, hidden) in line_slice.chars().zip(mask).take(self.pane.line_len(line)).skip(col) {
+            if !hidden {
+                width += char_display_width(ch);
+                if width > content_col {
+                    break;
+                }
+            }
+            col += 1;
+        }
+        col.min(self.pane.line_len(line))
+    }
+
+    /// 折り返し表示時の表示行・表示列をバッファ上の位置に変換
+    pub(crate) fn wrapped_screen_to_pos(&self, row: usize, display_col: usize) -> Option<(usize, usize)> {
+        let mut remaining = row;
+        let mut line = self.pane.scroll_offset;
+        while line < self.pane.buffer.len_lines() {
+            let starts = self.line_row_starts(line);
+            if remaining >= starts.len() {
+                remaining -= starts.len();
+                line = self.pane.next_visible_line(line);
+                continue;
+            }
+            // 折り返し位置より右をクリックした場合はその表示行の末尾に留める
+            let limit = starts.get(remaining + 1).map(|&next| next - 1);
+            let mut col = starts[remaining];
+            let mut width = 0;
+            for ch in self.pane.buffer.line(line).chars().skip(col) {
+                if ch == '\n' || limit == Some(col) {
+                    break;
+                }
+                let ch_width = char_display_width(ch);
+                if width + ch_width > display_col {
+                    break;
+                }
+                width += ch_width;
+                col += 1;
+            }
+            return Some((line, col));
+        }
+        None
+    }
+
+    pub(crate) fn get_highlighted_lines(&mut self, visible_height: usize, visible_width: usize) -> Vec<Line<'static>> {
+        // キャッシュを更新
+        self.update_cache();
+
+        let mut lines = Vec::with_capacity(visible_height);
+        let total_lines = self.pane.line_offsets.len().max(1);
+        let ln_width = self.line_number_width();
+        let content_width = visible_width.saturating_sub(ln_width);
+
+        let mut line_idx = self.pane.visible_line(self.pane.scroll_offset);
+        while lines.len() < visible_height {
+            let line_num = line_idx + 1;
+
+            if line_idx < total_lines {
+                // 行番号（折りたたんだ行とブックマークした行は区切りに印を表示）
+                let ln_span = if self.pane.fold_end_at(line_idx).is_some() {
+                    let ln_str = format!("{:>width$}…", line_num, width = ln_width - 1);
+                    Span::styled(ln_str, Style::default().fg(Color::Yellow))
+                } else if self.pane.bookmarks.binary_search(&line_idx).is_ok() {
+                    let ln_str = format!("{:>width$}>", line_num, width = ln_width - 1);
+                    Span::styled(ln_str, Style::default().fg(Color::Cyan))
+                } else {
+                    let ln_str = format!("{:>width$} ", line_num, width = ln_width - 1);
+                    Span::styled(ln_str, Style::default().fg(Color::DarkGray))
+                };
+
+                if let Some((line_text, line_start)) = self.get_line_from_cache(line_idx) {
+                    // 折り返し時は1行を複数の表示行に分割（継続行には行番号を表示しない）
+                    let row_starts = if self.wrapping() {
+                        wrap_row_starts(line_text.chars(), content_width)
+                    } else {
+                        vec![self.pane.horizontal_scroll]
+                    };
+                    for (row, &start_col) in row_starts.iter().enumerate() {
+                        if lines.len() >= visible_height {
+                            break;
+                        }
+                        let gutter = if row == 0 {
+                            ln_span.clone()
+                        } else {
+                            Span::raw(" ".repeat(ln_width))
+                        };
+                        let mut spans = vec![gutter];
+                        if self.pane.ansi_colors {
+                            spans.extend(self.build_spans_ansi(line_text, start_col, content_width, line_idx));
+                        } else if let Some(ref colors) = &self.pane.highlight_cache {
+                            spans.extend(self.build_spans_from_colors(line_text, line_start, colors, start_col, content_width, line_idx));
+                        } else {
+                            spans.extend(self.build_spans_simple(line_text, start_col, content_width, line_idx));
+                        }
+                        lines.push(Line::from(spans));
+                    }
+                } else {
+                    lines.push(Line::from(vec![ln_span]));
+                }
+            } else {
+                let ln_str = format!("{:>width$} ", "~", width = ln_width - 1);
+                lines.push(Line::from(Span::styled(ln_str, Style::default().fg(Color::DarkGray))));
+            }
+            // 折りたたんだ行は隠した範囲を飛ばす
+            line_idx = self.pane.next_visible_line(line_idx);
+        }
+
+        // ガイドの桁が横スクロール後の表示範囲内にあれば縦線を引く
+        if let Some(column) = self.wrap_guide_column {
+            let scroll = if self.wrapping() { 0 } else { self.pane.horizontal_scroll };
+            if column >= scroll && column - scroll < content_width {
+                for line in lines.iter_mut() {
+                    push_wrap_guide(line, ln_width + column - scroll);
+                }
+            }
+        }
+
+        lines
+    }
+
+    /// ミニマップの1行が表すバッファの行数（ファイル全体が収まるように縮小）
+    pub(crate) fn minimap_lines_per_row(&self) -> usize {
+        let height = self.minimap_area.height.saturating_sub(2).max(1) as usize;
+        self.pane.buffer.len_lines().div_ceil(height).max(1)
+    }
+
+    /// ミニマップの各行（文字のあるマスをハイライトの色のブロックで表し、表示中の範囲は背景を付ける）
+    pub(crate) fn minimap_lines(&self, width: usize, height: usize) -> Vec<Line<'static>> {
+        let lines_per_row = self.minimap_lines_per_row();
+        let total_lines = self.pane.line_offsets.len();
+        let visible_height = self.pane.editor_area.height.saturating_sub(2) as usize;
+        // 表示中の範囲（折りたたみで隠れた行も含む）
+        let last_visible = self.pane.lines_below(self.pane.scroll_offset, visible_height.saturating_sub(1));
+        let viewport = self.pane.scroll_offset..self.pane.next_visible_line(last_visible);
+
+        let mut rows = Vec::with_capacity(height);
+        for row in 0..height {
+            let first_line = row * lines_per_row;
+            if first_line >= total_lines {
+                break;
+            }
+            // 各マスの色（マス内の最初の空白以外の文字の色）
+            let mut cells: Vec<Option<Color>> = vec![None; width];
+            for line_idx in first_line..(first_line + lines_per_row.min(MINIMAP_SAMPLE_LINES)).min(total_lines) {
+                let Some((line_text, line_start)) = self.get_line_from_cache(line_idx) else {
+                    continue;
+                };
+                let mut display_col = 0;
+                for (byte_offset, ch) in line_text.char_indices() {
+                    let cell = display_col / MINIMAP_COLUMNS_PER_CELL;
+                    if cell >= width {
+                        break;
+                    }
+                    if !ch.is_whitespace() && cells[cell].is_none() {
+                        let color = self.pane.highlight_cache.as_ref()
+                            .and_then(|colors| colors.get(line_start + byte_offset))
+                            .filter(|&&color| color != Color::White)
+                            .copied()
+                            .unwrap_or(self.colors.text);
+                        cells[cell] = Some(color);
+                    }
+                    display_col += char_display_width(ch);
+                }
+            }
+
+            let in_viewport = first_line < viewport.end && first_line + lines_per_row > viewport.start;
+            let base = if in_viewport {
+                Style::default().bg(Color::DarkGray)
+            } else {
+                Style::default()
+            };
+            let spans: Vec<Span<'static>> = cells.into_iter()
+                .map(|cell| match cell {
+                    Some(color) => Span::styled("▄", base.fg(color)),
+                    None => Span::styled(" ", base),
+                })
+                .collect();
+            rows.push(Line::from(spans));
+        }
+        rows
+    }
+
+    /// 指定位置が検索マッチ内かどうかチェック
+    pub(crate) fn is_in_search_match(&self, line_idx: usize, col: usize) -> bool {
+        if !self.search_mode || self.search_query.is_empty() {
+            return false;
+        }
+        let query_len = self.search_query.chars().count();
+        for &(match_line, match_col) in &self.search_matches {
+            if match_line == line_idx && col >= match_col && col < match_col + query_len {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// 現在のマッチ位置かどうかチェック
+    pub(crate) fn is_current_match(&self, line_idx: usize, col: usize) -> bool {
+        if !self.search_mode || self.search_query.is_empty() {
+            return false;
+        }
+        if let Some(&(match_line, match_col)) = self.search_matches.get(self.search_index) {
+            let query_len = self.search_query.chars().count();
+            return match_line == line_idx && col >= match_col && col < match_col + query_len;
+        }
+        false
+    }
+
+    /// 指定位置が選択範囲内かどうかチェック
+    pub(crate) fn is_in_selection(&self, line_idx: usize, col: usize) -> bool {
+        if let Some(ref sel) = self.selection {
+            sel.contains(line_idx, col)
+        } else {
+            false
+        }
+    }
+
+    /// 現在行の背景（指定がなければ背景なし）
+    pub(crate) fn line_style(&self, line_idx: usize) -> Style {
+        match self.colors.current_line_bg {
+            Some(bg) if line_idx == self.pane.cursor_line => Style::default().bg(bg),
+            _ => Style::default(),
+        }
+    }
+
+    /// 表示行の末尾を表示幅まで背景で埋める（colは表示しきれなかった最初の文字、背景がなければ何もしない）
+    pub(crate) fn push_line_padding(&self, spans: &mut Vec<Span<'static>>, line_idx: usize, col: usize, visible_chars: usize, visible_width: usize) {
+        let style = if self.is_in_selection(line_idx, col) {
+            Style::default().bg(Color::Blue)
+        } else {
+            self.line_style(line_idx)
+        };
+        if style != Style::default() && visible_chars < visible_width {
+            spans.push(Span::styled(" ".repeat(visible_width - visible_chars), style));
+        }
+    }
+
+    /// 行末にある追加のカーソルを反転した空白で表示
+    pub(crate) fn push_line_end_cursor(&self, spans: &mut Vec<Span<'static>>, line_text: &str, line_idx: usize, end_col: usize, visible_chars: &mut usize, visible_width: usize) {
+        let line_len = line_text.chars().count();
+        if end_col == line_len && *visible_chars < visible_width && self.pane.has_cursor_at(line_idx, line_len) {
+            spans.push(Span::styled(" ", Style::default().add_modifier(Modifier::REVERSED)));
+            *visible_chars += 1;
+        }
+    }
+
+    pub(crate) fn build_spans_from_colors(&self, line_text: &str, line_start: usize, colors: &[Color], start_col: usize, visible_width: usize, line_idx: usize) -> Vec<Span<'static>> {
+        let mut result = Vec::new();
+        let mut current_style: Option<Style> = None;
+        let mut current_text = String::new();
+        let mut byte_offset = 0;
+        let mut visible_chars = 0;
+        let mut end_col = start_col;
+        let line_style = self.line_style(line_idx);
+
+        for (char_index, ch) in line_text.chars().enumerate() {
+            let byte_pos = line_start + byte_offset;
+            let fg_color = match colors.get(byte_pos) {
+                Some(Color::White) | None => self.colors.text,
+                Some(&color) => color,
+            };
+
+            // タブは4スペースに展開、エスケープ文字は端末に解釈されないよう記号で表示、その他は表示幅を取得
+            let (display_ch, char_width) = if ch == '\t' {
+                (' ', 4usize)
+            } else if ch == '\x1b' {
+                (ESCAPE_SYMBOL, 1)
+            } else {
+                (ch, ch.width().unwrap_or(1))
+            };
+
+            // 横スクロール（または折り返し）範囲内の文字のみ処理
+            if char_index >= start_col && visible_chars < visible_width {
+                // 表示幅が残り幅を超える場合は終了
+                if visible_chars + char_width > visible_width {
+                    break;
+                }
+
+                // ハイライト優先度: 追加のカーソル > 検索マッチ > 選択範囲 > スペルミス > 通常
+                let style = if self.pane.has_cursor_at(line_idx, char_index) {
+                    Style::default().fg(fg_color).add_modifier(Modifier::REVERSED)
+                } else if self.is_current_match(line_idx, char_index) {
+                    Style::default().fg(Color::Black).bg(Color::Yellow)
+                } else if self.is_in_search_match(line_idx, char_index) {
+                    Style::default().fg(fg_color).bg(Color::DarkGray)
+                } else if self.is_in_selection(line_idx, char_index) {
+                    Style::default().fg(Color::White).bg(Color::Blue)
+                } else if self.pane.is_misspelled(byte_pos) {
+                    line_style.fg(fg_color).add_modifier(Modifier::UNDERLINED).underline_color(Color::Red)
+                } else {
+                    line_style.fg(fg_color)
+                };
+
+                if current_style.is_none() {
+                    current_style = Some(style);
+                }
+
+                if Some(style) != current_style {
+                    if !current_text.is_empty() {
+                        result.push(Span::styled(current_text.clone(), current_style.unwrap()));
+                        current_text.clear();
+                    }
+                    current_style = Some(style);
+                }
+                // タブは複数スペースとして追加
+                if ch == '\t' {
+                    for _ in 0..char_width {
+                        current_text.push(' ');
+                    }
+                } else {
+                    current_text.push(display_ch);
+                }
+                visible_chars += char_width;
+                end_col = char_index + 1;
+            }
+
+            byte_offset += ch.len_utf8();
+        }
+
+        if !current_text.is_empty() {
+            if let Some(style) = current_style {
+                result.push(Span::styled(current_text, style));
+            }
+        }
+        self.push_line_end_cursor(&mut result, line_text, line_idx, end_col, &mut visible_chars, visible_width);
+        self.push_line_padding(&mut result, line_idx, end_col, visible_chars, visible_width);
+
+        result
+    }
+
+    /// ANSIのエスケープシーケンスを解釈した色で表示（カーソル・検索マッチ・選択範囲は他の表示と同じく上書き）
+    pub(crate) fn build_spans_ansi(&self, line_text: &str, start_col: usize, visible_width: usize, line_idx: usize) -> Vec<Span<'static>> {
+        let base = self.line_style(line_idx).fg(self.colors.text);
+        let (mut result, end_col, mut visible_chars) = ansi_to_spans(line_text, base, start_col, visible_width, |char_index, style| {
+            if self.pane.has_cursor_at(line_idx, char_index) {
+                style.add_modifier(Modifier::REVERSED)
+            } else if self.is_current_match(line_idx, char_index) {
+                Style::default().fg(Color::Black).bg(Color::Yellow)
+            } else if self.is_in_search_match(line_idx, char_index) {
+                style.bg(Color::DarkGray)
+            } else if self.is_in_selection(line_idx, char_index) {
+                Style::default().fg(Color::White).bg(Color::Blue)
+            } else {
+                style
+            }
+        });
+        self.push_line_end_cursor(&mut result, line_text, line_idx, end_col, &mut visible_chars, visible_width);
+        self.push_line_padding(&mut result, line_idx, end_col, visible_chars, visible_width);
+        result
+    }
+
+    pub(crate) fn build_spans_simple(&self, line_text: &str, start_col: usize, visible_width: usize, line_idx: usize) -> Vec<Span<'static>> {
+        let mut result = Vec::new();
+        let mut current_style: Option<Style> = None;
+        let mut current_text = String::new();
+        let mut visible_chars = 0;
+        let mut end_col = start_col;
+        let line_style = self.line_style(line_idx);
+
+        for (char_index, ch) in line_text.chars().enumerate() {
+            // タブは4スペースに展開、その他は表示幅を取得
+            let char_width = if ch == '\t' { 4usize } else { ch.width().unwrap_or(1) };
+
+            if char_index >= start_col && visible_chars < visible_width {
+                // 表示幅が残り幅を超える場合は終了
+                if visible_chars + char_width > visible_width {
+                    break;
+                }
+
+                // ハイライト優先度: 追加のカーソル > 検索マッチ > 選択範囲 > 通常
+                let style = if self.pane.has_cursor_at(line_idx, char_index) {
+                    Style::default().add_modifier(Modifier::REVERSED)
+                } else if self.is_current_match(line_idx, char_index) {
+                    Style::default().fg(Color::Black).bg(Color::Yellow)
+                } else if self.is_in_search_match(line_idx, char_index) {
+                    Style::default().bg(Color::DarkGray)
+                } else if self.is_in_selection(line_idx, char_index) {
+                    Style::default().fg(Color::White).bg(Color::Blue)
+                } else {
+                    line_style
+                };
+
+                if current_style.is_none() {
+                    current_style = Some(style);
+                }
+
+                if Some(style) != current_style {
+                    if !current_text.is_empty() {
+                        result.push(Span::styled(current_text.clone(), current_style.unwrap()));
+                        current_text.clear();
+                    }
+                    current_style = Some(style);
+                }
+                // タブは複数スペースとして追加
+                if ch == '\t' {
+                    for _ in 0..char_width {
+                        current_text.push(' ');
+                    }
+                } else {
+                    current_text.push(if ch == '\x1b' { ESCAPE_SYMBOL } else { ch });
+                }
+                visible_chars += char_width;
+                end_col = char_index + 1;
+            }
+        }
+
+        if !current_text.is_empty() {
+            if let Some(style) = current_style {
+                result.push(Span::styled(current_text, style));
+            } else {
+                result.push(Span::raw(current_text));
+            }
+        }
+        self.push_line_end_cursor(&mut result, line_text, line_idx, end_col, &mut visible_chars, visible_width);
+        self.push_line_padding(&mut result, line_idx, end_col, visible_chars, visible_width);
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn background_luminance_from_osc_response() {
+        assert_eq!(parse_background_is_light(b"\x1b]11;rgb:ffff/ffff/ffff\x07\x1b[0n"), Some(true));
+        assert_eq!(parse_background_is_light(b"\x1b]11;rgb:1e/1e/2e\x1b\\\x1b[0n"), Some(false));
+        assert_eq!(parse_background_is_light(b"\x1b]11;rgb:fdf6/e3e3/0000\x07"), Some(true));
+        assert_eq!(parse_background_is_light(b"\x1b[0n"), None);
+        assert_eq!(parse_background_is_light(b"\x1b]11;rgb:ffff/ffff\x07"), None);
+    }
+}